@@ -0,0 +1,441 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::errors::AssembleError;
+use crate::instruction::Instruction;
+
+// Assembler for the mnemonics of Cowgod's Chip-8 technical reference, the syntax that
+// `Syntax::Cowgod` disassembles to.
+//
+// - `;` starts a comment
+// - `name:` defines a label at the current address, a statement can follow it on the same line
+// - `name EQU value` and `DEFINE name value` define constants
+// - `DB` and `DW` emit comma separated bytes and big endian words
+// - `INCLUDE "path"` assembles another file in place, the path is relative to the including file
+// - numbers are decimal, hex with a `#`, `$` or `0x` prefix, or binary with a `%` or `0b` prefix
+// - values are sums and differences of numbers, labels and constants
+//
+// Mnemonics, directives and registers are case insensitive, labels and constants are not.
+
+// the address programs are loaded at by `Chip8::load_program`
+pub const PROGRAM_START: u16 = 0x200;
+
+// includes nested deeper than this are assumed to include themselves
+const MAX_INCLUDE_DEPTH: usize = 16;
+// same for constants that are defined in terms of other constants
+const MAX_CONSTANT_DEPTH: usize = 16;
+
+const MNEMONICS: [&str; 32] = [
+    "SYS", "CLS", "RET", "SCD", "SCU", "SCR", "SCL", "EXIT", "LOW", "HIGH", "JP", "CALL", "SE", "SNE", "SAVE",
+    "LOAD", "LD", "ADD", "OR", "AND", "XOR", "SUB", "SHR", "SUBN", "SHL", "RND", "DRW", "SKP", "SKNP",
+    "PLANE", "AUDIO", "PITCH",
+];
+
+const RESERVED: [&str; 10] = ["I", "DT", "ST", "K", "F", "HF", "B", "R", "LONG", "EQU"];
+
+#[derive(Clone, Debug)]
+struct Location {
+    file: String,
+    line: usize,
+}
+
+impl Location {
+    fn error(&self, message: String) -> AssembleError {
+        AssembleError::Syntax {
+            file: self.file.clone(),
+            line: self.line,
+            message,
+        }
+    }
+}
+
+enum StatementKind {
+    Instruction { mnemonic: String, operands: Vec<String> },
+    Bytes(Vec<String>),
+    Words(Vec<String>),
+}
+
+struct Statement {
+    location: Location,
+    kind: StatementKind,
+}
+
+#[derive(Clone, Copy)]
+enum Operand {
+    V(u8),
+    I,
+    IndirectI,
+    DT,
+    ST,
+    K,
+    F,
+    HF,
+    B,
+    R,
+    Long(i64),
+    Value(i64),
+}
+
+#[derive(Default)]
+struct Assembler {
+    statements: Vec<Statement>,
+    labels: HashMap<String, u16>,
+    constants: HashMap<String, String>,
+    // wider than an address, so running past the end of memory can be detected
+    address: u32,
+}
+
+// Assembles source into a rom that can be loaded with `Chip8::load_program`.
+// path is used in error messages and to resolve includes.
+pub fn assemble(source: &str, path: &Path) -> Result<Vec<u8>, AssembleError> {
+    let mut assembler = Assembler {
+        address: PROGRAM_START as u32,
+        ..Default::default()
+    };
+    assembler.read(source, path, 0)?;
+    assembler.emit()
+}
+
+pub fn assemble_file(path: &Path) -> Result<Vec<u8>, AssembleError> {
+    let source = read_source(path)?;
+    assemble(&source, path)
+}
+
+fn read_source(path: &Path) -> Result<String, AssembleError> {
+    fs::read_to_string(path).map_err(|source| AssembleError::Io {
+        path: path.display().to_string(),
+        source,
+    })
+}
+
+impl Assembler {
+    // first pass, collects the statements and the address of every label
+    fn read(&mut self, source: &str, path: &Path, depth: usize) -> Result<(), AssembleError> {
+        for (number, line) in source.lines().enumerate() {
+            let location = Location {
+                file: path.display().to_string(),
+                line: number + 1,
+            };
+            let mut text = line.split(';').next().unwrap().trim();
+
+            if let Some((name, rest)) = text.split_once(':') {
+                if is_identifier(name.trim()) {
+                    self.define_label(name.trim(), &location)?;
+                    text = rest.trim();
+                }
+            }
+            if text.is_empty() {
+                continue;
+            }
+
+            let (word, rest) = split_word(text);
+            match word.to_uppercase().as_str() {
+                "INCLUDE" => {
+                    let include = rest.strip_prefix('"').and_then(|rest| rest.strip_suffix('"'))
+                        .ok_or_else(|| location.error("INCLUDE expects a quoted path".to_string()))?;
+                    if depth >= MAX_INCLUDE_DEPTH {
+                        return Err(location.error(format!("Includes are nested too deep including {}", include)));
+                    }
+                    let include_path = path.parent().map_or_else(|| PathBuf::from(include), |parent| parent.join(include));
+                    let source = read_source(&include_path)?;
+                    self.read(&source, &include_path, depth + 1)?;
+                },
+                "DEFINE" => {
+                    let (name, value) = split_word(rest);
+                    self.define_constant(name, value, &location)?;
+                },
+                "DB" => {
+                    let values = split_operands(rest);
+                    self.advance(values.len() as u32, &location)?;
+                    self.statements.push(Statement { location, kind: StatementKind::Bytes(values) });
+                },
+                "DW" => {
+                    let values = split_operands(rest);
+                    self.advance(values.len() as u32 * 2, &location)?;
+                    self.statements.push(Statement { location, kind: StatementKind::Words(values) });
+                },
+                _ => {
+                    let (second, value) = split_word(rest);
+                    if second.eq_ignore_ascii_case("EQU") {
+                        self.define_constant(word, value, &location)?;
+                        continue;
+                    }
+
+                    let operands = split_operands(rest);
+                    // LD I, LONG NNNN is the only 4 byte instruction
+                    let size = if operands.iter().any(|operand| long_operand(operand).is_some()) { 4 } else { 2 };
+                    self.advance(size, &location)?;
+                    self.statements.push(Statement {
+                        location,
+                        kind: StatementKind::Instruction { mnemonic: word.to_uppercase(), operands },
+                    });
+                },
+            }
+        }
+        Ok(())
+    }
+
+    // second pass, every label is known now
+    fn emit(&self) -> Result<Vec<u8>, AssembleError> {
+        let mut rom = Vec::new();
+        for statement in &self.statements {
+            let location = &statement.location;
+            match &statement.kind {
+                StatementKind::Instruction { mnemonic, operands } => {
+                    let (instruction, long) = self.instruction(mnemonic, operands).map_err(|message| location.error(message))?;
+                    rom.extend_from_slice(&instruction.encode().to_be_bytes());
+                    if let Some(long) = long {
+                        rom.extend_from_slice(&long.to_be_bytes());
+                    }
+                },
+                StatementKind::Bytes(values) => {
+                    for value in values {
+                        let byte = self.value(value, 0).and_then(byte).map_err(|message| location.error(message))?;
+                        rom.push(byte);
+                    }
+                },
+                StatementKind::Words(values) => {
+                    for value in values {
+                        let word = self.value(value, 0).and_then(word).map_err(|message| location.error(message))?;
+                        rom.extend_from_slice(&word.to_be_bytes());
+                    }
+                },
+            }
+        }
+        Ok(rom)
+    }
+
+    fn advance(&mut self, size: u32, location: &Location) -> Result<(), AssembleError> {
+        self.address += size;
+        if self.address > 0x10000 {
+            return Err(location.error("Program does not fit in memory".to_string()));
+        }
+        Ok(())
+    }
+
+    fn check_symbol(&self, name: &str, location: &Location) -> Result<(), AssembleError> {
+        if !is_identifier(name) || register(name).is_some() || RESERVED.contains(&name.to_uppercase().as_str()) {
+            return Err(location.error(format!("{} can not be used as a name", name)));
+        }
+        if self.labels.contains_key(name) || self.constants.contains_key(name) {
+            return Err(location.error(format!("{} is already defined", name)));
+        }
+        Ok(())
+    }
+
+    fn define_label(&mut self, name: &str, location: &Location) -> Result<(), AssembleError> {
+        self.check_symbol(name, location)?;
+        self.labels.insert(name.to_string(), self.address as u16);
+        Ok(())
+    }
+
+    fn define_constant(&mut self, name: &str, value: &str, location: &Location) -> Result<(), AssembleError> {
+        self.check_symbol(name, location)?;
+        if value.is_empty() {
+            return Err(location.error(format!("{} has no value", name)));
+        }
+        self.constants.insert(name.to_string(), value.to_string());
+        Ok(())
+    }
+
+    // evaluates a sum of numbers and symbols
+    fn value(&self, expression: &str, depth: usize) -> Result<i64, String> {
+        let mut total = 0;
+        let mut sign = 1;
+        let mut term = String::new();
+        for c in expression.chars() {
+            if c != '+' && c != '-' {
+                term.push(c);
+                continue;
+            }
+            if term.trim().is_empty() {
+                // a unary sign
+                if c == '-' {
+                    sign = -sign;
+                }
+                continue;
+            }
+            total += sign * self.term(term.trim(), depth)?;
+            term.clear();
+            sign = if c == '-' { -1 } else { 1 };
+        }
+        if term.trim().is_empty() {
+            return Err(format!("Missing value in {}", expression.trim()));
+        }
+        Ok(total + sign * self.term(term.trim(), depth)?)
+    }
+
+    fn term(&self, term: &str, depth: usize) -> Result<i64, String> {
+        let lower = term.to_lowercase();
+        let (digits, radix) = if let Some(hex) = lower.strip_prefix('#').or_else(|| lower.strip_prefix('$')).or_else(|| lower.strip_prefix("0x")) {
+            (hex, 16)
+        }
+        else if let Some(bin) = lower.strip_prefix('%').or_else(|| lower.strip_prefix("0b")) {
+            (bin, 2)
+        }
+        else if lower.starts_with(|c: char| c.is_ascii_digit()) {
+            (lower.as_str(), 10)
+        }
+        else if let Some(address) = self.labels.get(term) {
+            return Ok(*address as i64);
+        }
+        else if let Some(value) = self.constants.get(term) {
+            if depth >= MAX_CONSTANT_DEPTH {
+                return Err(format!("Constant {} is defined in terms of itself", term));
+            }
+            return self.value(value, depth + 1);
+        }
+        else {
+            return Err(format!("Undefined symbol {}", term));
+        };
+        i64::from_str_radix(digits, radix).map_err(|_| format!("Invalid number {}", term))
+    }
+
+    fn operand(&self, text: &str) -> Result<Operand, String> {
+        if let Some(x) = register(text) {
+            return Ok(Operand::V(x));
+        }
+        if let Some(value) = long_operand(text) {
+            return Ok(Operand::Long(self.value(value, 0)?));
+        }
+        Ok(match text.to_uppercase().as_str() {
+            "I" => Operand::I,
+            "[I]" => Operand::IndirectI,
+            "DT" => Operand::DT,
+            "ST" => Operand::ST,
+            "K" => Operand::K,
+            "F" => Operand::F,
+            "HF" => Operand::HF,
+            "B" => Operand::B,
+            "R" => Operand::R,
+            _ => Operand::Value(self.value(text, 0)?),
+        })
+    }
+
+    // the instruction and the long address of LD I, LONG NNNN
+    fn instruction(&self, mnemonic: &str, operands: &[String]) -> Result<(Instruction, Option<u16>), String> {
+        use Operand::*;
+
+        let operands = operands.iter().map(|operand| self.operand(operand)).collect::<Result<Vec<_>, _>>()?;
+        let instruction = match (mnemonic, operands.as_slice()) {
+            ("SYS", [Value(nnn)]) => Instruction::Sys { nnn: address(*nnn)? },
+            ("CLS", []) => Instruction::ClearScreen,
+            ("RET", []) => Instruction::Return,
+            ("SCD", [Value(n)]) => Instruction::ScrollDown { n: nibble(*n)? },
+            ("SCU", [Value(n)]) => Instruction::ScrollUp { n: nibble(*n)? },
+            ("SCR", []) => Instruction::ScrollRight,
+            ("SCL", []) => Instruction::ScrollLeft,
+            ("EXIT", []) => Instruction::Exit,
+            ("LOW", []) => Instruction::LowRes,
+            ("HIGH", []) => Instruction::HighRes,
+            ("JP", [Value(nnn)]) => Instruction::Jump { nnn: address(*nnn)? },
+            ("JP", [V(0), Value(nnn)]) => {
+                let nnn = address(*nnn)?;
+                Instruction::JumpOffset { x: (nnn >> 8) as u8, nnn }
+            },
+            ("CALL", [Value(nnn)]) => Instruction::Call { nnn: address(*nnn)? },
+            ("SE", [V(x), Value(nn)]) => Instruction::SkipEqImm { x: *x, nn: byte(*nn)? },
+            ("SE", [V(x), V(y)]) => Instruction::SkipEqReg { x: *x, y: *y },
+            ("SNE", [V(x), Value(nn)]) => Instruction::SkipNeImm { x: *x, nn: byte(*nn)? },
+            ("SNE", [V(x), V(y)]) => Instruction::SkipNeReg { x: *x, y: *y },
+            ("SAVE", [V(x), V(y)]) => Instruction::SaveRange { x: *x, y: *y },
+            ("LOAD", [V(x), V(y)]) => Instruction::LoadRange { x: *x, y: *y },
+            ("LD", [V(x), Value(nn)]) => Instruction::LoadImm { x: *x, nn: byte(*nn)? },
+            ("LD", [V(x), V(y)]) => Instruction::Move { x: *x, y: *y },
+            ("LD", [V(x), DT]) => Instruction::LoadDelay { x: *x },
+            ("LD", [V(x), K]) => Instruction::WaitKey { x: *x },
+            ("LD", [V(x), IndirectI]) => Instruction::LoadRegisters { x: *x },
+            ("LD", [V(x), R]) => Instruction::LoadFlags { x: *x },
+            ("LD", [I, Value(nnn)]) => Instruction::LoadIndex { nnn: address(*nnn)? },
+            ("LD", [I, Long(long)]) => return Ok((Instruction::LoadLongIndex, Some(word(*long)?))),
+            ("LD", [DT, V(x)]) => Instruction::SetDelay { x: *x },
+            ("LD", [ST, V(x)]) => Instruction::SetSound { x: *x },
+            ("LD", [F, V(x)]) => Instruction::LoadFont { x: *x },
+            ("LD", [HF, V(x)]) => Instruction::LoadBigFont { x: *x },
+            ("LD", [B, V(x)]) => Instruction::StoreBcd { x: *x },
+            ("LD", [IndirectI, V(x)]) => Instruction::StoreRegisters { x: *x },
+            ("LD", [R, V(x)]) => Instruction::StoreFlags { x: *x },
+            ("ADD", [V(x), Value(nn)]) => Instruction::AddImm { x: *x, nn: byte(*nn)? },
+            ("ADD", [V(x), V(y)]) => Instruction::Add { x: *x, y: *y },
+            ("ADD", [I, V(x)]) => Instruction::AddIndex { x: *x },
+            ("OR", [V(x), V(y)]) => Instruction::Or { x: *x, y: *y },
+            ("AND", [V(x), V(y)]) => Instruction::And { x: *x, y: *y },
+            ("XOR", [V(x), V(y)]) => Instruction::Xor { x: *x, y: *y },
+            ("SUB", [V(x), V(y)]) => Instruction::Sub { x: *x, y: *y },
+            ("SUBN", [V(x), V(y)]) => Instruction::SubReverse { x: *x, y: *y },
+            // VY defaults to VX, which shifts VX in place with either shifting quirk
+            ("SHR", [V(x)]) => Instruction::ShiftRight { x: *x, y: *x },
+            ("SHR", [V(x), V(y)]) => Instruction::ShiftRight { x: *x, y: *y },
+            ("SHL", [V(x)]) => Instruction::ShiftLeft { x: *x, y: *x },
+            ("SHL", [V(x), V(y)]) => Instruction::ShiftLeft { x: *x, y: *y },
+            ("RND", [V(x), Value(nn)]) => Instruction::Random { x: *x, nn: byte(*nn)? },
+            ("DRW", [V(x), V(y), Value(n)]) => Instruction::Draw { x: *x, y: *y, n: nibble(*n)? },
+            ("SKP", [V(x)]) => Instruction::SkipKeyPressed { x: *x },
+            ("SKNP", [V(x)]) => Instruction::SkipKeyNotPressed { x: *x },
+            ("PLANE", [Value(n)]) => Instruction::SelectPlanes { n: nibble(*n)? },
+            ("AUDIO", []) => Instruction::LoadAudioPattern,
+            ("PITCH", [V(x)]) => Instruction::SetPitch { x: *x },
+            _ if MNEMONICS.contains(&mnemonic) => return Err(format!("Invalid operands for {}", mnemonic)),
+            _ => return Err(format!("Unknown instruction {}", mnemonic)),
+        };
+        Ok((instruction, None))
+    }
+}
+
+fn range(value: i64, min: i64, max: i64, what: &str) -> Result<i64, String> {
+    if value < min || value > max {
+        return Err(format!("{} does not fit in {}", value, what));
+    }
+    Ok(value)
+}
+
+// negative bytes and words are stored as two's complement
+fn byte(value: i64) -> Result<u8, String> {
+    range(value, -0x80, 0xFF, "a byte").map(|value| value as u8)
+}
+
+fn word(value: i64) -> Result<u16, String> {
+    range(value, -0x8000, 0xFFFF, "a word").map(|value| value as u16)
+}
+
+fn nibble(value: i64) -> Result<u8, String> {
+    range(value, 0, 0xF, "a nibble").map(|value| value as u8)
+}
+
+fn address(value: i64) -> Result<u16, String> {
+    range(value, 0, 0xFFF, "a 12 bit address").map(|value| value as u16)
+}
+
+fn register(text: &str) -> Option<u8> {
+    let mut chars = text.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some('v' | 'V'), Some(digit), None) => digit.to_digit(16).map(|x| x as u8),
+        _ => None,
+    }
+}
+
+fn long_operand(text: &str) -> Option<&str> {
+    let (word, rest) = split_word(text);
+    word.eq_ignore_ascii_case("LONG").then_some(rest)
+}
+
+fn is_identifier(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') &&
+        text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+// the first word of text and the trimmed rest
+fn split_word(text: &str) -> (&str, &str) {
+    match text.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim()),
+        None => (text, ""),
+    }
+}
+
+fn split_operands(text: &str) -> Vec<String> {
+    if text.is_empty() {
+        return Vec::new();
+    }
+    text.split(',').map(|operand| operand.trim().to_string()).collect()
+}
@@ -1,13 +1,59 @@
+use std::sync::{Arc, Mutex};
+
 use cpal::{traits::{DeviceTrait, HostTrait, StreamTrait}, SampleFormat, Stream, StreamError, SupportedStreamConfig};
 
 use crate::errors::BeeperError;
 
+// What the beeper plays while the sound timer is active.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sound {
+    // the classic 440 Hz buzzer
+    Buzzer,
+    // XO-CHIP 128 bit, 1 bit per sample audio pattern, played at a rate set by the pitch register
+    Pattern { pattern: [u8; 16], pitch: u8 },
+}
+
+impl Sound {
+    // XO-CHIP pattern playback rate in bits per second, 4000 Hz at the default pitch of 64
+    pub fn pattern_rate(pitch: u8) -> f32 {
+        4000.0 * 2f32.powf((pitch as f32 - 64.0) / 48.0)
+    }
+}
+
+// Where a Machine sends its sound, once per frame.
+pub trait AudioSink {
+    // the sound to play until the next call, None while the sound timer is inactive
+    fn set_sound(&mut self, sound: Option<Sound>);
+}
+
+// for running without audio
+pub struct Silence;
+
+impl AudioSink for Silence {
+    fn set_sound(&mut self, _: Option<Sound>) {}
+}
+
+// The output stream keeps running and plays silence while there is no sound. The stream cannot be
+// sent to other threads, so machines get a BeeperSink that shares the sound with it instead.
 pub struct Beeper {
     device: cpal::Device,
     supported_config: SupportedStreamConfig,
     stream: Stream,
-    vol: f32
+    vol: f32,
+    sound: Arc<Mutex<Option<Sound>>>,
 }
+
+#[derive(Clone)]
+pub struct BeeperSink {
+    sound: Arc<Mutex<Option<Sound>>>,
+}
+
+impl AudioSink for BeeperSink {
+    fn set_sound(&mut self, sound: Option<Sound>) {
+        *self.sound.lock().unwrap() = sound;
+    }
+}
+
 impl Beeper {
     pub fn new(vol: f32) -> Result<Self, BeeperError>  {
         let host = cpal::default_host();
@@ -15,17 +61,20 @@ impl Beeper {
         let supported_config = device.default_output_config().unwrap();
         let config = supported_config.config();
         let sample_format = supported_config.sample_format();
+        let sound = Arc::new(Mutex::new(None));
 
         let streamres = match sample_format {
-            SampleFormat::F32 => run::<f32>(&device, &config, vol),
-            SampleFormat::I16 => run::<i16>(&device, &config, vol),
-            SampleFormat::U16 => run::<u16>(&device, &config, vol),
+            SampleFormat::F32 => run::<f32>(&device, &config, vol, sound.clone()),
+            SampleFormat::I16 => run::<i16>(&device, &config, vol, sound.clone()),
+            SampleFormat::U16 => run::<u16>(&device, &config, vol, sound.clone()),
         }?;
+        streamres.play()?;
         Ok(Self {
             device,
             supported_config,
             stream: streamres,
             vol,
+            sound,
         })
     }
     pub fn set_vol(&mut self, vol: f32) -> Result<(), BeeperError> {
@@ -33,34 +82,46 @@ impl Beeper {
             let sample_format = self.supported_config.sample_format();
             let config = self.supported_config.config();
             self.stream = match sample_format {
-                SampleFormat::F32 => run::<f32>(&self.device, &config, vol),
-                SampleFormat::I16 => run::<i16>(&self.device, &config, vol),
-                SampleFormat::U16 => run::<u16>(&self.device, &config, vol),
+                SampleFormat::F32 => run::<f32>(&self.device, &config, vol, self.sound.clone()),
+                SampleFormat::I16 => run::<i16>(&self.device, &config, vol, self.sound.clone()),
+                SampleFormat::U16 => run::<u16>(&self.device, &config, vol, self.sound.clone()),
             }?;
+            self.stream.play()?;
             self.vol = vol;
         }
         Ok(())
     }
-    pub fn play(&self) {
-        self.stream.play().unwrap();
-    }
-    pub fn pause(&self) {
-        self.stream.pause().unwrap();
+    pub fn sink(&self) -> BeeperSink {
+        BeeperSink { sound: self.sound.clone() }
     }
 }
 
-pub fn run<T>(device: &cpal::Device, config: &cpal::StreamConfig, vol: f32) -> Result<Stream, BeeperError>
+pub fn run<T>(device: &cpal::Device, config: &cpal::StreamConfig, vol: f32, sound: Arc<Mutex<Option<Sound>>>) -> Result<Stream, BeeperError>
 where
     T: cpal::Sample,
 {
     let sample_rate = config.sample_rate.0 as f32;
     let channels = config.channels as usize;
 
-    // Produce a sinusoid of maximum amplitude.
+    // Produce a sinusoid of maximum amplitude for the buzzer.
     let mut sample_clock = 0f32;
-    let mut next_value = move || {
-        sample_clock = (sample_clock + 1.0) % sample_rate;
-        ((sample_clock * 440.0 * 2.0 * std::f32::consts::PI / sample_rate).sin() / 6.0) * vol
+    // Position in the audio pattern, in bits.
+    let mut pattern_position = 0f32;
+    let mut next_value = move |sound: &Option<Sound>| {
+        match sound {
+            None => 0.0,
+            Some(Sound::Buzzer) => {
+                sample_clock = (sample_clock + 1.0) % sample_rate;
+                ((sample_clock * 440.0 * 2.0 * std::f32::consts::PI / sample_rate).sin() / 6.0) * vol
+            },
+            Some(Sound::Pattern { pattern, pitch }) => {
+                pattern_position = (pattern_position + Sound::pattern_rate(*pitch) / sample_rate) % 128.0;
+                let bit = pattern_position as usize;
+                // a square wave, high for set bits and low for unset bits
+                let amplitude = if pattern[bit / 8] & (0x80 >> (bit % 8)) != 0 { 1.0 } else { -1.0 };
+                (amplitude / 6.0) * vol
+            },
+        }
     };
 
     #[cfg(not(target_arch = "wasm32"))]
@@ -73,7 +134,9 @@ where
         device.build_output_stream(
             config,
             move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
-                write_data(data, channels, &mut next_value)
+                // the sound is only read once per buffer, to keep the lock out of the per sample loop
+                let sound = *sound.lock().unwrap();
+                write_data(data, channels, &mut || next_value(&sound))
             },
             err_fn,
         )?
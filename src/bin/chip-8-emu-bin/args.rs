@@ -1,28 +1,193 @@
-use chip_8_emu::options::{Options, RGB};
-use clap::{Arg, Command};
+use std::path::Path;
 
-pub struct BinArgs {
+use chip_8_emu::{chip8::MachineMode, disasm::Syntax, options::{Options, RGB}, quirks::QuirksPreset, trace::TraceFormat};
+use clap::{Arg, ArgMatches, Command};
+
+pub enum BinCommand {
+    Run(RunArgs),
+    Headless(HeadlessArgs),
+    Disasm(DisasmArgs),
+    Assemble(AssembleArgs),
+    StateExport(StateExportArgs),
+    StateImport(StateImportArgs),
+}
+
+pub struct RunArgs {
     pub options: Options,
     pub rom_path: String,
+    // the cpu is driven from a REPL on stdin instead of running freely
+    pub debug: bool,
+    // every executed instruction is written to this file
+    pub trace_path: Option<String>,
+    pub trace_format: TraceFormat,
+    // bytes kept for rewinding, 0 disables it
+    pub rewind_budget: usize,
+    // frames between rewind snapshots
+    pub rewind_interval: u64,
+    // the keys pressed are recorded into this movie
+    pub record_path: Option<String>,
+    // the keys are played back from this movie
+    pub play_path: Option<String>,
+}
+
+pub struct HeadlessArgs {
+    pub options: Options,
+    pub rom_path: String,
+    // the run stops after this many frames, 600 or the length of the movie if none
+    pub frames: Option<u64>,
+    // the run stops early once the rom jumps to itself
+    pub exit_on_loop: bool,
+    pub input_path: Option<String>,
+    // the keys are played back from this movie, which also sets up the machine
+    pub movie_path: Option<String>,
+    // stdout if none
+    pub screen_path: Option<String>,
+    pub registers_path: Option<String>,
+}
+
+pub struct DisasmArgs {
+    pub rom_path: String,
+    pub syntax: Syntax,
+}
+
+pub struct AssembleArgs {
+    pub source_path: String,
+    pub output_path: String,
+}
+
+pub struct StateExportArgs {
+    pub state_path: String,
+    // stdout if none
+    pub output_path: Option<String>,
 }
 
-pub fn parse_args() -> BinArgs {
-    let m = Command::new(env!("CARGO_PKG_NAME"))
+pub struct StateImportArgs {
+    pub json_path: String,
+    pub output_path: String,
+    // the state is tagged with the hash of this rom
+    pub rom_path: Option<String>,
+}
+
+pub fn parse_args() -> BinCommand {
+    // running a ROM is the default, so its arguments are also accepted without the run subcommand
+    let m = with_run_args(Command::new(env!("CARGO_PKG_NAME")))
     .author(env!("CARGO_PKG_AUTHORS"))
     .version(env!("CARGO_PKG_VERSION"))
     .about("Interpretting Emulator for Chip-8")
+    .args_conflicts_with_subcommands(true)
+    .subcommand_negates_reqs(true)
 
-    .arg(Arg::new("rom_path").required(true).help("The path of the ROM that is to be loaded into the emulator. If a '.state' file is loaded, the emulator will resume from that save state."))
+    .subcommand(with_run_args(Command::new("run").about("Run a ROM in a window.")))
+    .subcommand(
+        with_machine_args(Command::new("headless").about("Run a ROM without a window or audio, as fast as possible, and dump the final display and registers."))
+        .arg(Arg::new("rom_path").required(true).help("The path of the ROM that is to be run."))
+        .arg(Arg::new("frames").required(false).short('f').long("frames").takes_value(true).help("The number of 60 Hz frames to run for, unless the ROM halts or crashes first. Defaults to 600, or the length of the movie."))
+        .arg(Arg::new("exit_on_loop").required(false).long("exit-on-loop").help("Stop once the ROM jumps to itself, which test ROMs do when they are done."))
+        .arg(Arg::new("input").required(false).long("input").takes_value(true).help("A script of the keys held down from a frame on, see the README for the format."))
+        .arg(Arg::new("movie").required(false).long("movie").takes_value(true).conflicts_with("input").help("Play back a movie recorded with --record. The machine is set up as it was recorded, ignoring the options."))
+        .arg(Arg::new("screen").required(false).short('s').long("screen").takes_value(true).help("Write the final display to this file, as a PNG if it ends in '.png' and as text otherwise. Defaults to text on stdout."))
+        .arg(Arg::new("registers").required(false).short('r').long("registers").takes_value(true).help("Write the final registers as JSON to this file. Defaults to stdout."))
+    )
+    .subcommand(
+        Command::new("disasm").about("Disassemble a ROM, tracing the code reachable from 0x200.")
+        .arg(Arg::new("rom_path").required(true).help("The path of the ROM that is to be disassembled."))
+        .arg(Arg::new("syntax").required(false).short('s').long("syntax").help("The syntax of the mnemonics.").possible_values(Syntax::NAMES).default_value("octo"))
+    )
+    .subcommand(
+        Command::new("assemble").about("Assemble a source file into a ROM.")
+        .arg(Arg::new("source_path").required(true).help("The path of the source that is to be assembled."))
+        .arg(Arg::new("output").required(false).short('o').long("output").takes_value(true).help("The path the ROM is written to, defaults to the source path with a '.ch8' extension."))
+    )
+    .subcommand(
+        Command::new("state").about("Convert save states to and from human readable JSON.")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("export").about("Write a save state as JSON, with memory as a hex dump and the display as ASCII art.")
+            .arg(Arg::new("state_path").required(true).help("The path of the save state that is to be exported."))
+            .arg(Arg::new("output").required(false).short('o').long("output").takes_value(true).help("The path the JSON is written to. Defaults to stdout."))
+        )
+        .subcommand(
+            Command::new("import").about("Turn JSON written by state export back into a save state.")
+            .arg(Arg::new("json_path").required(true).help("The path of the JSON that is to be imported."))
+            .arg(Arg::new("output").required(false).short('o').long("output").takes_value(true).help("The path the state is written to, defaults to the JSON path with a '.state' extension."))
+            .arg(Arg::new("rom").required(false).long("rom").takes_value(true).help("The ROM the state belongs to, it is then refused with other ROMs."))
+        )
+    )
+    .get_matches();
+
+    match m.subcommand() {
+        Some(("run", m)) => BinCommand::Run(parse_run_args(m)),
+        Some(("headless", m)) => BinCommand::Headless(HeadlessArgs {
+            options: parse_options(m),
+            rom_path: m.value_of("rom_path").unwrap().to_string(),
+            frames: m.value_of("frames").map(|frames| frames.parse::<u64>().unwrap()),
+            exit_on_loop: m.is_present("exit_on_loop"),
+            input_path: m.value_of("input").map(|path| path.to_string()),
+            movie_path: m.value_of("movie").map(|path| path.to_string()),
+            screen_path: m.value_of("screen").map(|path| path.to_string()),
+            registers_path: m.value_of("registers").map(|path| path.to_string()),
+        }),
+        Some(("disasm", m)) => BinCommand::Disasm(DisasmArgs {
+            rom_path: m.value_of("rom_path").unwrap().to_string(),
+            syntax: m.value_of("syntax").unwrap().parse::<Syntax>().unwrap(),
+        }),
+        Some(("assemble", m)) => {
+            let source_path = m.value_of("source_path").unwrap().to_string();
+            let output_path = m.value_of("output").map_or_else(
+                || Path::new(&source_path).with_extension("ch8").to_str().unwrap().to_string(),
+                |output| output.to_string(),
+            );
+            BinCommand::Assemble(AssembleArgs { source_path, output_path })
+        },
+        Some(("state", m)) => match m.subcommand() {
+            Some(("export", m)) => BinCommand::StateExport(StateExportArgs {
+                state_path: m.value_of("state_path").unwrap().to_string(),
+                output_path: m.value_of("output").map(|path| path.to_string()),
+            }),
+            Some(("import", m)) => {
+                let json_path = m.value_of("json_path").unwrap().to_string();
+                let output_path = m.value_of("output").map_or_else(
+                    || Path::new(&json_path).with_extension("state").to_str().unwrap().to_string(),
+                    |output| output.to_string(),
+                );
+                BinCommand::StateImport(StateImportArgs { json_path, output_path, rom_path: m.value_of("rom").map(|path| path.to_string()) })
+            },
+            _ => unreachable!("state requires a subcommand"),
+        },
+        _ => BinCommand::Run(parse_run_args(&m)),
+    }
+}
+
+// the options of the emulated machine, shared by the window and headless runs
+fn with_machine_args(command: Command) -> Command {
+    command
     .arg(Arg::new("invert_colors").required(false).short('i').long("invert-colors").help("Invert colors of the screen of the emulator."))
     .arg(Arg::new("hz").required(false).short('h').long("hz").help("The amount of loops that the emulator runs in one second.").default_value("500"))
-    .arg(Arg::new("foreground_color").required(false).short('f').long("fg").help("The color in Hex that will be the foreground color.").default_value("FFFFFF"))
-    .arg(Arg::new("background_color").required(false).short('b').long("bg").help("The color in Hex that will be the background color.").default_value("000000"))
+    .arg(Arg::new("cycles_per_frame").required(false).short('c').long("cycles-per-frame").takes_value(true).help("The amount of instructions run in each 60 Hz frame, overrides --hz."))
+    .arg(Arg::new("palette").required(false).short('p').long("palette").help("Four comma separated colors in Hex for the background, plane 1, plane 2 and both planes.").default_value("000000,FFFFFF,AAAAAA,555555"))
+    .arg(Arg::new("mode").required(false).short('m').long("mode").help("The machine that is emulated, XO-CHIP has 64 KiB of memory and bitplanes.").possible_values(MachineMode::NAMES).default_value("chip8"))
+    .arg(Arg::new("quirks").required(false).short('q').long("quirks").help("The quirks profile that the ambiguous opcodes are interpreted with.").possible_values(QuirksPreset::NAMES).default_value("modern"))
+    .arg(Arg::new("seed").required(false).long("seed").takes_value(true).help("Seed the random numbers of CXNN, the same seed and input replay a run exactly."))
+}
+
+fn with_run_args(command: Command) -> Command {
+    with_machine_args(command)
+    .arg(Arg::new("rom_path").required(true).help("The path of the ROM that is to be loaded into the emulator. If a '.state' file is loaded, the emulator will resume from that save state."))
+    .arg(Arg::new("debug").required(false).short('d').long("debug").help("Start paused in a debugger REPL on the terminal, type help for its commands."))
+    .arg(Arg::new("trace").required(false).short('t').long("trace").takes_value(true).help("Write a line for every executed instruction to this file, see the README for the format."))
+    .arg(Arg::new("trace_format").required(false).long("trace-format").help("The format of the trace, binary is more compact for long runs.").possible_values(TraceFormat::NAMES).default_value("text"))
+    .arg(Arg::new("rewind_budget").required(false).long("rewind-budget").help("KiB of memory used for rewinding, 0 disables rewinding.").default_value("1024"))
+    .arg(Arg::new("rewind_interval").required(false).long("rewind-interval").help("Frames between the snapshots that are rewound to, there are 60 frames in a second.").default_value("6"))
     .arg(Arg::new("volume").required(false).short('v').long("volume").help("Volume of the beep as a float.").default_value("0.2"))
+    .arg(Arg::new("record").required(false).long("record").takes_value(true).conflicts_with_all(&["play", "debug"]).help("Record the keys pressed into this movie file, which is written when the window is closed. States cannot be loaded and rewinding is off while recording."))
+    .arg(Arg::new("play").required(false).long("play").takes_value(true).conflicts_with("debug").help("Play back a movie recorded with --record. The machine is set up as it was recorded, ignoring the options, and the keyboard takes over once the movie ends."))
     .before_help("
 Keybindings:
 
-- F5 for saving state.
-- F6 for loading state.
+- F5 for saving state to the selected slot.
+- F6 for loading state from the selected slot.
+- Ctrl and 0 to 9 to select a save slot.
+- Hold Backspace to rewind.
 
 Keypad:        Keyboard:
 ---------      ---------
@@ -34,18 +199,42 @@ Keypad:        Keyboard:
 ---------      ---------
 |A|0|B|F|      |Z|X|C|V|
 ---------      ---------")
-    .get_matches();
+}
 
-    return BinArgs {
-        options: Options {
-            invert_colors: m.is_present("invert_colors"),
-            hz: m.value_of("hz").unwrap().parse::<u64>().unwrap(),
-            fg: hex_to_rgb(u32::from_str_radix(m.value_of("foreground_color").unwrap(), 16).unwrap()),
-            bg: hex_to_rgb(u32::from_str_radix(m.value_of("background_color").unwrap(), 16).unwrap()),
-            vol: m.value_of("volume").unwrap().parse::<f32>().unwrap()
-        },
+// headless runs have no volume
+fn parse_options(m: &ArgMatches) -> Options {
+    Options {
+        invert_colors: m.is_present("invert_colors"),
+        hz: m.value_of("hz").unwrap().parse::<u64>().unwrap(),
+        cycles_per_frame: m.value_of("cycles_per_frame").map(|cycles| cycles.parse::<u64>().unwrap()),
+        palette: parse_palette(m.value_of("palette").unwrap()),
+        vol: m.try_get_one::<String>("volume").ok().flatten().map_or(0.0, |vol| vol.parse::<f32>().unwrap()),
+        mode: m.value_of("mode").unwrap().parse::<MachineMode>().unwrap(),
+        quirks: m.value_of("quirks").unwrap().parse::<QuirksPreset>().unwrap(),
+        seed: m.value_of("seed").map(|seed| seed.parse::<u64>().unwrap()),
+    }
+}
+
+fn parse_run_args(m: &ArgMatches) -> RunArgs {
+    RunArgs {
+        options: parse_options(m),
         rom_path: m.value_of("rom_path").unwrap().to_string(),
-    };
+        debug: m.is_present("debug"),
+        trace_path: m.value_of("trace").map(|path| path.to_string()),
+        trace_format: m.value_of("trace_format").unwrap().parse::<TraceFormat>().unwrap(),
+        rewind_budget: m.value_of("rewind_budget").unwrap().parse::<usize>().unwrap() * 1024,
+        rewind_interval: m.value_of("rewind_interval").unwrap().parse::<u64>().unwrap().max(1),
+        record_path: m.value_of("record").map(|path| path.to_string()),
+        play_path: m.value_of("play").map(|path| path.to_string()),
+    }
+}
+
+fn parse_palette(palette: &str) -> [RGB; 4] {
+    let colors: Vec<RGB> = palette
+        .split(',')
+        .map(|hex| hex_to_rgb(u32::from_str_radix(hex.trim(), 16).unwrap()))
+        .collect();
+    colors.try_into().unwrap_or_else(|_| panic!("The palette must have exactly four colors."))
 }
 
 fn hex_to_rgb(hex: u32) -> RGB {
@@ -1,9 +1,8 @@
 use std::path::{Path, PathBuf};
 use std::fs::{File, metadata};
-use std::io::Read;
+use std::io::{BufWriter, Read, Write};
 
-use chip_8_emu::chip8::Chip8;
-use savefile::{save_file, load_file};
+use chip_8_emu::{chip8::Chip8, errors::SaveStateError, movie::Movie, options::Options, savestate::SaveState};
 
 
 pub fn get_file_as_byte_vec(filename: &str) -> Vec<u8> {
@@ -14,26 +13,97 @@ pub fn get_file_as_byte_vec(filename: &str) -> Vec<u8> {
     buffer
 }
 
-pub fn save_state(filename: &Path, chip8inst: &Chip8) {
-    save_file(filename, 0, chip8inst).unwrap_or_else(|x| {
-        println!("{}", x);
-    });
-    println!("State saved: {}", filename.to_str().unwrap());
-}
-
-pub fn load_state(filename: &Path, chip8inst: &mut Chip8) {
-    if filename.exists() {
-        match load_file::<Chip8, PathBuf>(filename.to_path_buf(), 0) {
-            Ok(state) => {
-                *chip8inst = state;
-            },
-            Err(x) => {
-                println!("{}", x);
-            }
+pub fn read_state(filename: &Path) -> Result<SaveState, SaveStateError> {
+    SaveState::read(&mut File::open(filename)?)
+}
+
+pub fn write_state(filename: &Path, state: &SaveState) -> Result<(), SaveStateError> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+    state.write(&mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+// Saves the state, returning it for its thumbnail.
+pub fn save_state(filename: &Path, chip8inst: &mut Chip8, rom_hash: Option<u32>, options: &Options) -> Option<SaveState> {
+    let saved = SaveState::capture(chip8inst, rom_hash, Some(options)).and_then(|state| {
+        if let Some(dir) = filename.parent() {
+            std::fs::create_dir_all(dir)?;
         }
-        println!("State loaded: {}", filename.to_str().unwrap());
+        write_state(filename, &state)?;
+        Ok(state)
+    });
+    match saved {
+        Ok(state) => {
+            println!("State saved: {}", filename.display());
+            Some(state)
+        },
+        Err(x) => {
+            println!("{}", x);
+            None
+        },
     }
-    else {
+}
+
+// Loads the state into chip8inst, unless it was saved with a different rom. The state is returned for its metadata.
+pub fn load_state(filename: &Path, chip8inst: &mut Chip8, rom_hash: Option<u32>) -> Option<SaveState> {
+    if !filename.exists() {
         println!("No state file found!");
+        return None;
+    }
+
+    let loaded = read_state(filename).and_then(|state| {
+        if let Some(rom_hash) = rom_hash {
+            state.check_rom(rom_hash)?;
+        }
+        state.restore(chip8inst)?;
+        Ok(state)
+    });
+    match loaded {
+        Ok(state) => {
+            println!("State loaded: {}", filename.display());
+            Some(state)
+        },
+        Err(x) => {
+            println!("{}", x);
+            None
+        },
     }
-}
\ No newline at end of file
+}
+
+pub fn read_movie(filename: &Path) -> Result<Movie, String> {
+    std::fs::read_to_string(filename).map_err(|x| x.to_string())?.parse::<Movie>()
+}
+
+pub fn write_movie(filename: &Path, movie: &Movie) -> std::io::Result<()> {
+    std::fs::write(filename, movie.to_string())
+}
+
+// The per-user directory the emulator keeps its data in, the rom folder may be read-only.
+// $XDG_DATA_HOME or ~/.local/share on Linux, ~/Library/Application Support on macOS and %APPDATA% on Windows.
+pub fn data_dir() -> PathBuf {
+    let base = if cfg!(target_os = "windows") {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    }
+    else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    }
+    else {
+        std::env::var_os("XDG_DATA_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+    };
+    // falls back to the working directory when there is no home
+    base.unwrap_or_default().join(env!("CARGO_PKG_NAME"))
+}
+
+// The file of a save slot. Slots are kept per rom, by its name and hash so that copies of a rom share them.
+pub fn slot_path(rom_path: &Path, rom_hash: Option<u32>, slot: u8) -> PathBuf {
+    let name = rom_path.file_stem().map_or_else(|| "rom".into(), |stem| stem.to_string_lossy());
+    let dir = match rom_hash {
+        Some(hash) => format!("{}-{:08X}", name, hash),
+        None => name.into_owned(),
+    };
+    data_dir().join("states").join(dir).join(format!("{}.state", slot))
+}
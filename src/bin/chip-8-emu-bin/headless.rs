@@ -0,0 +1,144 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use chip_8_emu::{audio::Silence, chip8::{Chip8, StepOutcome}, errors::Chip8Error, machine::{InputScript, Machine, SystemClock}, options::RGB, rng::Xorshift, utils::render_ascii};
+use serde_json::json;
+
+use crate::args::HeadlessArgs;
+use crate::fstools::{get_file_as_byte_vec, read_movie};
+
+// Why a headless run stopped.
+enum Exit {
+    Frames,
+    Halted,
+    Loop,
+    Crashed(Chip8Error),
+}
+
+pub fn headless(args: HeadlessArgs) {
+    let script = match &args.input_path {
+        Some(path) => {
+            let script = std::fs::read_to_string(path).unwrap_or_else(|x| fail(format!("Could not read {}: {}", path, x)));
+            script.parse::<InputScript>().unwrap_or_else(|x| fail(format!("{}: {}", path, x)))
+        },
+        None => InputScript::default(),
+    };
+
+    let movie = args.movie_path.as_ref().map(|path| {
+        read_movie(Path::new(path)).unwrap_or_else(|x| fail(format!("{}: {}", path, x)))
+    });
+
+    let rom = get_file_as_byte_vec(args.rom_path.as_str());
+    let (chip8, hz) = match &movie {
+        // the movie sets up the machine it was recorded with, the options are ignored
+        Some(movie) => {
+            let chip8 = movie.machine(&rom).unwrap_or_else(|x| fail(format!("{}: {}", args.movie_path.as_ref().unwrap(), x)));
+            (chip8, movie.hz)
+        },
+        None => {
+            let mut chip8 = Chip8::new(args.options.mode, args.options.quirks.into());
+            if let Some(seed) = args.options.seed {
+                chip8.rng = Xorshift::new(seed);
+            }
+            chip8.load_program(&rom);
+            (chip8, args.options.cycles_per_second())
+        },
+    };
+    let frames = args.frames.or(movie.as_ref().map(|movie| movie.frames)).unwrap_or(600);
+
+    // only run_frame is used, so the clock is never read
+    let mut machine = Machine::new(chip8, hz, Box::new(SystemClock::new()), Box::new(Silence));
+    if let Some(movie) = movie {
+        machine.play_movie(movie);
+    }
+
+    let exit = loop {
+        if machine.frames() >= frames {
+            break Exit::Frames;
+        }
+        if args.exit_on_loop && machine.is_stuck() {
+            break Exit::Loop;
+        }
+        if let Some(keys) = script.keys_at(machine.frames()) {
+            machine.chip8.key_state = keys;
+        }
+        match machine.run_frame() {
+            Ok(StepOutcome::Executed | StepOutcome::Drew) => {},
+            Ok(StepOutcome::Halted) => break Exit::Halted,
+            Err(err) => break Exit::Crashed(err),
+        }
+    };
+
+    match &args.screen_path {
+        Some(path) if path.to_lowercase().ends_with(".png") => {
+            write_png(Path::new(path), &machine.chip8, &args.options.render_palette())
+                .unwrap_or_else(|x| fail(format!("Could not write {}: {}", path, x)));
+        },
+        Some(path) => {
+            std::fs::write(path, render_ascii(&machine.chip8)).unwrap_or_else(|x| fail(format!("Could not write {}: {}", path, x)));
+        },
+        None => print!("{}", render_ascii(&machine.chip8)),
+    }
+
+    let registers = serde_json::to_string_pretty(&registers_json(&machine, &exit)).unwrap();
+    match &args.registers_path {
+        Some(path) => std::fs::write(path, registers + "\n").unwrap_or_else(|x| fail(format!("Could not write {}: {}", path, x))),
+        None => println!("{}", registers),
+    }
+
+    // a crash fails the run, so ci notices it
+    if let Exit::Crashed(err) = exit {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+fn registers_json(machine: &Machine, exit: &Exit) -> serde_json::Value {
+    let chip8 = &machine.chip8;
+    json!({
+        "exit": match exit {
+            Exit::Frames => "frames",
+            Exit::Halted => "halted",
+            Exit::Loop => "loop",
+            Exit::Crashed(_) => "crashed",
+        },
+        "error": match exit {
+            Exit::Crashed(err) => Some(err.to_string()),
+            _ => None,
+        },
+        "frames": machine.frames(),
+        "cycles": machine.cycles(),
+        "pc": chip8.pc,
+        "opcode": chip8.opcode,
+        "i": chip8.i,
+        "v": chip8.v_registers,
+        "sp": chip8.stack_pointer,
+        "stack": &chip8.jump_stack[..chip8.stack_pointer as usize],
+        "delay_timer": chip8.delay_timer,
+        "sound_timer": chip8.sound_timer,
+        "rpl_flags": chip8.rpl_flags,
+        "hires": chip8.hires,
+    })
+}
+
+// one png pixel per display pixel, colored with the palette
+fn write_png(path: &Path, chip8: &Chip8, palette: &[RGB; 4]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), chip8.display_width() as u32, chip8.display_height() as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let data: Vec<u8> = chip8.display
+        .iter()
+        .flat_map(|&pixel| {
+            let color = &palette[(pixel & 0b11) as usize];
+            [color.r, color.g, color.b]
+        })
+        .collect();
+    encoder.write_header()?.write_image_data(&data)?;
+    Ok(())
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
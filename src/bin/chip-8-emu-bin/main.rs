@@ -1,92 +1,258 @@
 mod fstools;
 mod args;
+mod headless;
 
-use std::sync::{Arc, RwLock};
+use std::sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 
-use chip_8_emu::{audio::Beeper, chip8::Chip8, input::parse_input, utils::render_texture_to_target};
-use fstools::{get_file_as_byte_vec, load_state, save_state};
+use args::{AssembleArgs, BinCommand, DisasmArgs, RunArgs, StateExportArgs, StateImportArgs};
+use chip_8_emu::{assembler::assemble_file, audio::{AudioSink, Beeper, Silence}, chip8::{Chip8, LORES_HEIGHT, LORES_WIDTH}, debugger::{location, Command, Debugger}, disasm::Disassembly, errors::Chip8Error, input::{parse_input, parse_slot_key}, machine::{Machine, SystemClock, FRAME_DURATION}, movie::Movie, rewind::RewindBuffer, rng::Xorshift, savestate::{rom_hash, SaveState, Thumbnail}, trace::Tracer, utils::{draw_slot_overlay, render_texture_to_target, resize_target_to_display, CRASH_PALETTE}};
+use fstools::{get_file_as_byte_vec, load_state, read_movie, read_state, save_state, slot_path, write_movie, write_state};
 use pixels::{Pixels, SurfaceTexture};
 use winit::{
-    event::{ElementState, Event, VirtualKeyCode, WindowEvent},
-    event_loop::{ControlFlow, EventLoop},
+    event::{ElementState, Event, ModifiersState, VirtualKeyCode, WindowEvent},
+    event_loop::{ControlFlow, EventLoop, EventLoopProxy},
     window::WindowBuilder,
 };
 
+// how long the save slot picker stays on screen after switching slots
+const SLOT_OVERLAY_DURATION: Duration = Duration::from_secs(2);
+
+// the save slot picker, drawn over the display until it times out
+struct SlotOverlay {
+    slot: u8,
+    // none if the slot is empty
+    thumbnail: Option<Thumbnail>,
+    until: Instant,
+}
+
 fn main() {
     // args
-    let args = crate::args::parse_args();
+    match crate::args::parse_args() {
+        BinCommand::Run(args) => run(args),
+        BinCommand::Headless(args) => headless::headless(args),
+        BinCommand::Disasm(args) => disasm(args),
+        BinCommand::Assemble(args) => assemble(args),
+        BinCommand::StateExport(args) => state_export(args),
+        BinCommand::StateImport(args) => state_import(args),
+    }
+}
+
+fn assemble(args: AssembleArgs) {
+    let rom = assemble_file(std::path::Path::new(&args.source_path)).unwrap_or_else(|x| {
+        eprintln!("{}", x);
+        std::process::exit(1);
+    });
+    std::fs::write(&args.output_path, &rom).unwrap_or_else(|x| {
+        eprintln!("Could not write {}: {}", args.output_path, x);
+        std::process::exit(1);
+    });
+    println!("Assembled {} bytes to {}", rom.len(), args.output_path);
+}
+
+fn state_export(args: StateExportArgs) {
+    let mut chip8 = Chip8::default();
+    read_state(std::path::Path::new(&args.state_path))
+        .and_then(|state| state.restore(&mut chip8))
+        .unwrap_or_else(|x| {
+            eprintln!("{}: {}", args.state_path, x);
+            std::process::exit(1);
+        });
+    let json = chip8.to_json() + "\n";
+    match &args.output_path {
+        Some(path) => std::fs::write(path, json).unwrap_or_else(|x| {
+            eprintln!("Could not write {}: {}", path, x);
+            std::process::exit(1);
+        }),
+        None => print!("{}", json),
+    }
+}
 
-    // setup speed
-    // devide 2 as fetch and decode is on same loop
-    let runhz: u64 = args.options.hz;
-    let delay: u64 = 1000 / runhz;
-    let satisfiedruntimes: u64 = (1000 / 60) /delay;
+fn state_import(args: StateImportArgs) {
+    let json = std::fs::read_to_string(&args.json_path).unwrap_or_else(|x| {
+        eprintln!("Could not read {}: {}", args.json_path, x);
+        std::process::exit(1);
+    });
+    let mut chip8 = Chip8::from_json(&json).unwrap_or_else(|x| {
+        eprintln!("{}: {}", args.json_path, x);
+        std::process::exit(1);
+    });
+    let romhash = args.rom_path.as_ref().map(|path| rom_hash(&get_file_as_byte_vec(path)));
+    SaveState::capture(&mut chip8, romhash, None)
+        .and_then(|state| write_state(std::path::Path::new(&args.output_path), &state))
+        .unwrap_or_else(|x| {
+            eprintln!("Could not write {}: {}", args.output_path, x);
+            std::process::exit(1);
+        });
+    println!("Imported {} to {}", args.json_path, args.output_path);
+}
 
-    // setup cpu instance
-    let mut chip8inst = Chip8::default();
-    chip8inst.display = [args.options.invert_colors as u8; 2048];
+fn disasm(args: DisasmArgs) {
+    let rom = get_file_as_byte_vec(args.rom_path.as_str());
+    let disassembly = Disassembly::trace(&rom, 0x200, 0x200);
+    print!("{}", disassembly.render(args.syntax));
+}
 
-    // load rom/state into chip8inst
+fn run(args: RunArgs) {
     let rompath = args.rom_path.as_str();
-    if rompath.ends_with(".state") {
-        crate::fstools::load_state(std::path::Path::new(rompath), &mut chip8inst)
+    if rompath.ends_with(".state") && (args.record_path.is_some() || args.play_path.is_some()) {
+        eprintln!("Movies start at power on, they need a ROM rather than a state.");
+        std::process::exit(1);
+    }
+
+    // setup cpu instance, a recording needs a seed so one is picked if none is given
+    let mut chip8inst = Chip8::new(args.options.mode, args.options.quirks.into());
+    let seed = args.options.seed.or_else(|| args.record_path.as_ref().map(|_| rand::random()));
+    if let Some(seed) = seed {
+        chip8inst.rng = Xorshift::new(seed);
+    }
+    let mut hz = args.options.cycles_per_second();
+
+    // load rom/state into chip8inst, states saved later on are tagged with the hash of the rom
+    let mut movie = None;
+    let romhash = if rompath.ends_with(".state") {
+        load_state(std::path::Path::new(rompath), &mut chip8inst, None).and_then(|state| state.rom_hash)
     }
     else {
-        chip8inst.load_program(&get_file_as_byte_vec(rompath));
+        let rom = get_file_as_byte_vec(rompath);
+        chip8inst.load_program(&rom);
+        if let Some(path) = &args.play_path {
+            // the movie sets up the machine it was recorded with, the options are ignored
+            let played = read_movie(std::path::Path::new(path)).unwrap_or_else(|x| {
+                eprintln!("{}: {}", path, x);
+                std::process::exit(1);
+            });
+            chip8inst = played.machine(&rom).unwrap_or_else(|x| {
+                eprintln!("{}: {}", path, x);
+                std::process::exit(1);
+            });
+            hz = played.hz;
+            movie = Some(played);
+        }
+        if let Some(seed) = seed.filter(|_| args.record_path.is_some()) {
+            movie = Some(Movie::new(&rom, seed, args.options.mode, chip8inst.quirks, hz));
+        }
+        Some(rom_hash(&rom))
+    };
+
+    // the stream lives on this thread, the machine only gets a sink that shares the sound with it
+    let beeper = Beeper::new(args.options.vol).ok().filter(|_| args.options.vol > 0.0);
+    if beeper.is_none() {
+        println!("Audio not initialized!");
+    }
+    let audio: Box<dyn AudioSink + Send> = match &beeper {
+        Some(beeper) => Box::new(beeper.sink()),
+        None => Box::new(Silence),
+    };
+    let mut machine = Machine::new(chip8inst, hz, Box::new(SystemClock::new()), audio);
+    match movie {
+        Some(movie) if args.record_path.is_some() => machine.record_movie(movie),
+        Some(movie) => machine.play_movie(movie),
+        None => {},
     }
-    let chip8arc = Arc::new(RwLock::new(chip8inst));
+    let machinearc = Arc::new(Mutex::new(machine));
 
-    let loopchip8 = chip8arc.clone();
+    let tracer = args.trace_path.as_ref().map(|path| {
+        Tracer::create(std::path::Path::new(path), args.trace_format).unwrap_or_else(|x| {
+            eprintln!("Could not create trace {}: {}", path, x);
+            std::process::exit(1);
+        })
+    });
+
+    // set when the rom crashes, the emulator stops running until a state is loaded
+    let crasharc: Arc<RwLock<Option<Chip8Error>>> = Arc::new(RwLock::new(None));
+    // set while the rewind key is held
+    let rewindingarc = Arc::new(AtomicBool::new(false));
+
+    let event_loop = EventLoop::new();
+    // the cpu thread wakes the window up once per frame to present the display
+    let loopproxy = event_loop.create_proxy();
+
+    let loopmachine = machinearc.clone();
+    let loopcrash = crasharc.clone();
+    let looprewinding = rewindingarc.clone();
     std::thread::spawn(move || {
-        let beeper = Beeper::new(args.options.vol);
-        let beeperexist = beeper.is_ok() && args.options.vol > 0.0;
-        if !beeperexist {
-            println!("Audio not initialized!");
+        if args.debug {
+            debug(loopmachine, tracer, loopproxy);
+            return;
         }
+        if let Some(tracer) = tracer {
+            loopmachine.lock().unwrap().set_tracer(tracer);
+        }
+
+        let mut rewind = RewindBuffer::new(args.rewind_budget);
+        // the frame the last snapshot was taken at
+        let mut rewindframe = 0;
+        // frames the rewind key has been held for
+        let mut rewindingframes = 0;
 
-        let mut runtimes = 0;
         loop {
-            let next_frame_time = std::time::Instant::now() + std::time::Duration::from_millis(delay);
-            
-            // timer stuff
-            if runtimes >= satisfiedruntimes {
-                if loopchip8.read().unwrap().delay_timer > 0 {
-                    loopchip8.write().unwrap().delay_timer -= 1;
-                }
-                if loopchip8.read().unwrap().sound_timer > 0 {
-                    if beeperexist {
-                        beeper.as_ref().unwrap().play();
+            let mut machine = loopmachine.lock().unwrap();
+
+            // while rewinding or crashed the cpu is stopped, and when rewinding a snapshot is restored every rewind interval.
+            // movies cannot be rewound, they would no longer match the run.
+            let movie = machine.is_recording_movie() || machine.is_playing_movie();
+            let rewinding = args.rewind_budget > 0 && looprewinding.load(Ordering::Relaxed) && !movie;
+            if rewinding || loopcrash.read().unwrap().is_some() {
+                if rewinding {
+                    if rewindingframes % args.rewind_interval == 0 {
+                        match rewind.rewind(&mut machine.chip8) {
+                            // rewinding out of a crash resumes the emulator
+                            Ok(true) => *loopcrash.write().unwrap() = None,
+                            Ok(false) => {},
+                            Err(x) => println!("{}", x),
+                        }
                     }
-                    loopchip8.write().unwrap().sound_timer -= 1;
+                    rewindingframes += 1;
                 }
-                else if beeperexist {
-                    beeper.as_ref().unwrap().pause();
-                }
-
-                runtimes = 0;
+                machine.silence();
+                machine.sync();
+                drop(machine);
+                loopproxy.send_event(()).ok();
+                std::thread::sleep(FRAME_DURATION);
+                continue;
             }
-            runtimes += 1;
-
+            rewindingframes = 0;
 
-            // cycle cpu
-            loopchip8.write().unwrap().single_cycle();
+            if let Err(err) = machine.run_due() {
+                println!("{}", err);
+                *loopcrash.write().unwrap() = Some(err);
+            }
+            if let Some(err) = machine.take_trace_error() {
+                println!("Tracing stopped: {}", err);
+            }
 
-            if next_frame_time > std::time::Instant::now() {
-                std::thread::sleep(next_frame_time - std::time::Instant::now());
+            if args.rewind_budget > 0 && machine.frames() >= rewindframe + args.rewind_interval {
+                if let Err(x) = rewind.push(&mut machine.chip8) {
+                    println!("{}", x);
+                }
+                rewindframe = machine.frames();
             }
+
+            let sleep = machine.until_next_frame();
+            drop(machine);
+            loopproxy.send_event(()).ok();
+            std::thread::sleep(sleep);
         }
     });
 
     // setup opengl
-    let event_loop = EventLoop::new();
     let window = WindowBuilder::new().build(&event_loop).unwrap();
     let mut pixels = {
         let window_size = window.inner_size();
         let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
-        Pixels::new(64, 32, surface_texture).unwrap()
+        Pixels::new(LORES_WIDTH as u32, LORES_HEIGHT as u32, surface_texture).unwrap()
     };
 
-    let eventloopchip8 = chip8arc.clone();
+    let eventloopmachine = machinearc.clone();
+    let eventloopcrash = crasharc.clone();
+    let eventlooprewinding = rewindingarc.clone();
+    let palette = args.options.render_palette();
+    let mut crashshown = false;
+    let mut modifiers = ModifiersState::empty();
+    // F5 and F6 save to and load from the selected slot
+    let mut slot = 0;
+    let mut slotoverlay: Option<SlotOverlay> = None;
 
     event_loop.run(move |ev, _, control_flow| {
         *control_flow = ControlFlow::Wait;
@@ -95,46 +261,168 @@ fn main() {
             Event::WindowEvent {
                 event: WindowEvent::CloseRequested,
                 window_id,
-            } if window_id == window.id() => *control_flow = ControlFlow::Exit,
+            } if window_id == window.id() => {
+                if let Some(path) = &args.record_path {
+                    if let Some(movie) = eventloopmachine.lock().unwrap().take_movie() {
+                        match write_movie(std::path::Path::new(path), &movie) {
+                            Ok(()) => println!("Recorded {} frames to {}", movie.frames, path),
+                            Err(x) => eprintln!("Could not write {}: {}", path, x),
+                        }
+                    }
+                }
+                *control_flow = ControlFlow::Exit
+            },
+            Event::UserEvent(()) => window.request_redraw(),
             Event::RedrawRequested(_) => {
-                render_texture_to_target(&eventloopchip8.read().unwrap().display, pixels.frame_mut(), &args.options.fg, &args.options.bg);
+                let machine = eventloopmachine.lock().unwrap();
+                let chip8 = &machine.chip8;
+                resize_target_to_display(&mut pixels, chip8);
+
+                // crash screen, the last frame is shown with the crash palette and the error in the title
+                let crash = *eventloopcrash.read().unwrap();
+                if crash.is_some() != crashshown {
+                    crashshown = crash.is_some();
+                    match crash {
+                        Some(err) => window.set_title(&format!("{} - {}", env!("CARGO_PKG_NAME"), err)),
+                        None => window.set_title(env!("CARGO_PKG_NAME")),
+                    }
+                }
+                let renderpalette = if crashshown { &CRASH_PALETTE } else { &palette };
+                slotoverlay = slotoverlay.take().filter(|overlay| overlay.until > Instant::now());
+                match &slotoverlay {
+                    Some(overlay) => {
+                        let mut display = chip8.display.clone();
+                        draw_slot_overlay(&mut display, chip8.display_width(), overlay.slot, overlay.thumbnail.as_ref());
+                        render_texture_to_target(&display, pixels.frame_mut(), renderpalette);
+                    },
+                    None => render_texture_to_target(&chip8.display, pixels.frame_mut(), renderpalette),
+                }
                 pixels.render().unwrap();
             }
             Event::WindowEvent { window_id: _, event: window_ev } => match window_ev {
+                WindowEvent::ModifiersChanged(state) => modifiers = state,
                 WindowEvent::KeyboardInput {input, device_id: _, is_synthetic: _ } => {
-                    if let Some((key, pressed)) = parse_input(input) {
-                        eventloopchip8.write().unwrap().key_state[key] = pressed as u8;
-                    }
                     let pressed = (input.state == ElementState::Pressed) as u8;
+                    let rompath = std::path::Path::new(args.rom_path.as_str());
+
+                    // ctrl and the number row select the save slot, the number keys are not passed on to the rom
+                    let slotkey = input.virtual_keycode.and_then(parse_slot_key).filter(|_| modifiers.ctrl() && pressed == 1);
+                    if let Some(selected) = slotkey {
+                        slot = selected;
+                        slotoverlay = Some(SlotOverlay {
+                            slot,
+                            thumbnail: read_state(&slot_path(rompath, romhash, slot)).ok().map(|state| state.thumbnail),
+                            until: Instant::now() + SLOT_OVERLAY_DURATION,
+                        });
+                        window.request_redraw();
+                    }
+                    else if let Some((key, pressed)) = parse_input(input) {
+                        // a movie that is playing holds the keys until it ends, a recording picks them up every frame
+                        let mut machine = eventloopmachine.lock().unwrap();
+                        if !machine.is_playing_movie() {
+                            machine.chip8.key_state[key] = pressed as u8;
+                        }
+                    }
                     if let Some(virtual_keycode) = input.virtual_keycode {
                         match virtual_keycode {
-                            VirtualKeyCode::F5 => {
-                                if pressed == 1 {
-                                    let rompath = std::path::Path::new(args.rom_path.as_str());
-                                    let statepath = rompath.with_extension("state");
-                
-                                    save_state(&statepath, &eventloopchip8.read().unwrap());
-                                }
+                            VirtualKeyCode::F5 if pressed == 1 => {
+                                let statepath = slot_path(rompath, romhash, slot);
+
+                                save_state(&statepath, &mut eventloopmachine.lock().unwrap().chip8, romhash, &args.options);
                             },
-                            VirtualKeyCode::F6 => {
-                                if pressed == 1 {
-                                    let rompath = std::path::Path::new(args.rom_path.as_str());
-                                    let statepath = rompath.with_extension("state");
-                
-                                    load_state(&statepath, &mut eventloopchip8.write().unwrap())
+                            VirtualKeyCode::F6 if pressed == 1 => {
+                                let statepath = slot_path(rompath, romhash, slot);
+                                let mut machine = eventloopmachine.lock().unwrap();
+
+                                if machine.is_recording_movie() || machine.is_playing_movie() {
+                                    println!("States cannot be loaded while a movie is recorded or played.");
+                                }
+                                else if load_state(&statepath, &mut machine.chip8, romhash).is_some() {
+                                    *eventloopcrash.write().unwrap() = None;
                                 }
                             },
+                            VirtualKeyCode::Back => {
+                                eventlooprewinding.store(pressed == 1, Ordering::Relaxed);
+                            },
                             _ => {}
                         }
                     }
                 }
                 WindowEvent::Resized(size) => {
                     pixels.resize_surface(size.width, size.height).unwrap();
+                    window.request_redraw();
                 }
                 _ => ()
             },
             _ => (),
         }
-        window.request_redraw()
     });
-}
\ No newline at end of file
+}
+
+// REPL that drives the cpu instead of the free running loop, the window keeps showing the display.
+fn debug(machine: Arc<Mutex<Machine>>, tracer: Option<Tracer>, proxy: EventLoopProxy<()>) {
+    // lines are read on their own thread, so pressing enter can pause a running rom
+    let (sender, receiver) = std::sync::mpsc::channel::<String>();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lines() {
+            if sender.send(line.unwrap()).is_err() {
+                break;
+            }
+        }
+    });
+
+    // the debugger ticks the timers itself, so it can run backwards
+    let mut debugger = Debugger::new(machine.lock().unwrap().cycles_per_frame());
+    if let Some(tracer) = tracer {
+        debugger.set_tracer(tracer);
+    }
+    println!("Type help for a list of commands.");
+    println!("{}", location(&machine.lock().unwrap().chip8));
+    loop {
+        if debugger.is_running() {
+            if receiver.try_recv().is_ok() {
+                debugger.pause();
+                println!("Paused");
+                println!("{}", location(&machine.lock().unwrap().chip8));
+                continue;
+            }
+
+            let next_frame_time = std::time::Instant::now() + FRAME_DURATION;
+            {
+                let mut machine = machine.lock().unwrap();
+                for _ in 0..machine.cycles_per_frame() {
+                    if let Some(reason) = debugger.cycle(&mut machine.chip8) {
+                        println!("{}", reason);
+                        println!("{}", location(&machine.chip8));
+                        break;
+                    }
+                }
+                machine.update_audio();
+            }
+            proxy.send_event(()).ok();
+            if next_frame_time > std::time::Instant::now() {
+                std::thread::sleep(next_frame_time - std::time::Instant::now());
+            }
+            continue;
+        }
+
+        machine.lock().unwrap().silence();
+        print!("(chip-8) ");
+        std::io::Write::flush(&mut std::io::stdout()).unwrap();
+        // stdin has been closed
+        let Ok(line) = receiver.recv() else {
+            std::process::exit(0);
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match line.parse::<Command>() {
+            Ok(Command::Quit) => std::process::exit(0),
+            Ok(command) => {
+                print!("{}", debugger.execute(&mut machine.lock().unwrap().chip8, command));
+                proxy.send_event(()).ok();
+            },
+            Err(err) => println!("{}", err),
+        }
+    }
+}
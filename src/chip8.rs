@@ -1,15 +1,34 @@
+use std::fmt;
 use std::io::{Read, Write};
+use std::str::FromStr;
 
 use savefile::{load, save, SavefileError};
 use savefile_derive::Savefile;
-use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tsify_next::Tsify;
+
+use crate::{audio::Sound, errors::Chip8Error, instruction::{decode, Instruction}, quirks::{LoadStoreQuirk, Quirks}, rng::Xorshift, watchpoint::{is_watched, Access, WatchHit, Watchpoint}};
 
 
 // 0x000-0x1FF - Chip 8 interpreter (contains font set in emu)
-// 0x050-0x0A0 - Used for the built in 4x5 pixel font set (0-F)
-// 0x200-0xFFF - Program ROM and work RAM
+// 0x000-0x050 - Used for the built in 4x5 pixel font set (0-F)
+// 0x050-0x0F0 - Used for the built in 8x10 pixel SCHIP font set (0-F)
+// 0x200-0xFFF - Program ROM and work RAM (0x200-0xFFFF in XO-CHIP mode)
+
+pub const LORES_WIDTH: usize = 64;
+pub const LORES_HEIGHT: usize = 32;
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+
+pub const MEMORY_SIZE: usize = 0x1000;
+pub const XO_CHIP_MEMORY_SIZE: usize = 0x10000;
+
+const BIG_FONTSET_ADDRESS: usize = 0x050;
 
-static FONTSET: [u8; 80] = [
+// savefile version of save_state, bump it when adding a field and mark the field with #[savefile_versions = "N.."]
+pub const STATE_VERSION: u32 = 1;
+
+pub static FONTSET: [u8; 80] = [
 	0xF0, 0x90, 0x90, 0x90, 0xF0,		// 0
 	0x20, 0x60, 0x20, 0x20, 0x70,		// 1
 	0xF0, 0x10, 0xF0, 0x80, 0xF0,		// 2
@@ -28,12 +47,87 @@ static FONTSET: [u8; 80] = [
 	0xF0, 0x80, 0xF0, 0x80, 0x80		// F
 ];
 
-#[derive(Savefile)]
+static BIG_FONTSET: [u8; 160] = [
+	0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF,		// 0
+	0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF,		// 1
+	0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF,		// 2
+	0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF,		// 3
+	0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03,		// 4
+	0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF,		// 5
+	0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF,		// 6
+	0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18,		// 7
+	0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF,		// 8
+	0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF,		// 9
+	0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3,		// A
+	0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC,		// B
+	0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C,		// C
+	0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC,		// D
+	0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF,		// E
+	0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0		// F
+];
+
+// The machine being emulated, SCHIP instructions are available in both modes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Savefile, Tsify, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+pub enum MachineMode {
+    #[default]
+    Chip8,
+    // 64 KiB of memory, bitplanes and the XO-CHIP instructions
+    Xochip,
+}
+
+impl MachineMode {
+    pub const NAMES: [&'static str; 2] = ["chip8", "xochip"];
+
+    pub fn memory_size(&self) -> usize {
+        match self {
+            MachineMode::Chip8 => MEMORY_SIZE,
+            MachineMode::Xochip => XO_CHIP_MEMORY_SIZE,
+        }
+    }
+}
+
+impl FromStr for MachineMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "chip8" | "chip-8" | "schip" | "superchip" => Ok(Self::Chip8),
+            "xochip" | "xo-chip" => Ok(Self::Xochip),
+            _ => Err(format!("Unknown machine mode: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for MachineMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MachineMode::Chip8 => write!(f, "chip8"),
+            MachineMode::Xochip => write!(f, "xochip"),
+        }
+    }
+}
+
+// What happened during a single cycle that executed without errors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StepOutcome {
+    Executed,
+    // DXYN has been executed with the display wait quirk, the driver ends the frame until the next vblank
+    Drew,
+    // 00FD has been executed, no further instructions will be executed
+    Halted,
+}
+
+// Serialized with savefile for save states, and with serde as human readable json, see to_json.
+#[derive(Savefile, Serialize, Deserialize)]
 pub struct Chip8 {
     
     // current opcode
     pub opcode: u16,
-    pub memory: [u8; 4096],
+    // MEMORY_SIZE long, or XO_CHIP_MEMORY_SIZE long in XO-CHIP mode
+    #[serde(with = "crate::json::hex_memory")]
+    pub memory: Vec<u8>,
 
     // V registers
     pub v_registers: [u8; 16],
@@ -46,38 +140,182 @@ pub struct Chip8 {
     pub delay_timer: u8,
     pub sound_timer: u8,
 
+    // XO-CHIP audio pattern loaded with F002, the buzzer is used until one is loaded
+    pub audio_pattern: Option<[u8; 16]>,
+    // XO-CHIP pitch register set with FX3A, the pattern plays at 4000 Hz at the default of 64
+    pub pitch: u8,
+
     // stack used to remember the current location before a jump is performed.
     pub jump_stack: [u16; 16],
-    // system has 16 levels of stack, to remember which level, a pointer is used.
+    // system has 16 levels of stack, to remember which level, a pointer to the next free level is used.
     pub stack_pointer: u16,
 
     // hex based keypad 0x0-0xF
     pub key_state: [u8; 16],
-
-    pub display: [u8; 2048],
+    // the key FX0A has seen pressed and is waiting to be released
+    pub waiting_key: Option<u8>,
+
+    // one byte per pixel, LORES_WIDTH * LORES_HEIGHT or HIRES_WIDTH * HIRES_HEIGHT long
+    // each bit of a pixel is one of the XO-CHIP bitplanes, giving an index into a four color palette
+    #[serde(with = "crate::json::ascii_display")]
+    pub display: Vec<u8>,
+    // XO-CHIP bitplanes that drawing, clearing and scrolling operate on, selected with FN01
+    pub planes: u8,
+    // SCHIP 128x64 high resolution mode
+    pub hires: bool,
+
+    // SCHIP RPL user flags, saved and loaded with FX75/FX85
+    pub rpl_flags: [u8; 16],
+    // set once 00FD has been executed, the interpreter stops executing instructions
+    pub halted: bool,
+
+    pub mode: MachineMode,
+    // behaviour of the ambiguous opcodes
+    pub quirks: Quirks,
+    // random number generator for CXNN, saved with the rest of the state
+    pub rng: Xorshift,
+
+    // memory watchpoints and the accesses that hit them, taken with take_watch_hits, neither is saved
+    #[savefile_ignore]
+    #[savefile_introspect_ignore]
+    #[serde(skip)]
+    pub watchpoints: Vec<Watchpoint>,
+    #[savefile_ignore]
+    #[savefile_introspect_ignore]
+    #[serde(skip)]
+    pub watch_hits: Vec<WatchHit>,
 }
 
 impl Default for Chip8 {
     fn default() -> Self {
+        Self::new(MachineMode::default(), Quirks::default())
+    }
+}
+
+impl Chip8 {
+    pub fn new(mode: MachineMode, quirks: Quirks) -> Self {
         let mut chip8 = Chip8 {
             opcode: 0,
-            memory: [0; 4096],
+            memory: vec![0; mode.memory_size()],
             v_registers: [0; 16],
             i: 0,
             pc: 0x200,
             delay_timer: 0,
             sound_timer: 0,
+            audio_pattern: None,
+            pitch: 64,
             jump_stack: [0; 16],
             stack_pointer: 0,
             key_state: [0; 16],
-            display: [0; 2048]
+            waiting_key: None,
+            display: vec![0; LORES_WIDTH * LORES_HEIGHT],
+            planes: 1,
+            hires: false,
+            rpl_flags: [0; 16],
+            halted: false,
+            mode,
+            quirks,
+            rng: Xorshift::from_entropy(),
+            watchpoints: Vec::new(),
+            watch_hits: Vec::new(),
         };
         chip8.load_font_set(FONTSET);
+        chip8.load_big_font_set(BIG_FONTSET);
         chip8
     }
-}
 
-impl Chip8 {
+    // Builders for setting up a machine in a known state, mostly for tests:
+    // Chip8::default().with_v(0x1, 0x20).with_opcode(0x8014).
+
+    pub fn with_v(mut self, x: usize, value: u8) -> Self {
+        self.v_registers[x] = value;
+        self
+    }
+
+    pub fn with_i(mut self, i: u16) -> Self {
+        self.i = i;
+        self
+    }
+
+    pub fn with_pc(mut self, pc: u16) -> Self {
+        self.pc = pc;
+        self
+    }
+
+    pub fn with_memory(mut self, address: usize, bytes: &[u8]) -> Self {
+        self.memory[address..address + bytes.len()].copy_from_slice(bytes);
+        self
+    }
+
+    // writes the opcode at pc, so the next single_cycle executes it
+    pub fn with_opcode(self, opcode: u16) -> Self {
+        let pc = self.pc as usize;
+        self.with_memory(pc, &opcode.to_be_bytes())
+    }
+
+    // pushes the return addresses in order, the last one is on top
+    pub fn with_stack(mut self, addresses: &[u16]) -> Self {
+        for &address in addresses {
+            self.jump_stack[self.stack_pointer as usize] = address;
+            self.stack_pointer += 1;
+        }
+        self
+    }
+
+    pub fn with_key(mut self, key: usize, pressed: bool) -> Self {
+        self.key_state[key] = pressed as u8;
+        self
+    }
+
+    pub fn with_timers(mut self, delay_timer: u8, sound_timer: u8) -> Self {
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self
+    }
+
+    pub fn with_quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = Xorshift::new(seed);
+        self
+    }
+
+    pub fn display_width(&self) -> usize {
+        if self.hires { HIRES_WIDTH } else { LORES_WIDTH }
+    }
+
+    pub fn display_height(&self) -> usize {
+        if self.hires { HIRES_HEIGHT } else { LORES_HEIGHT }
+    }
+
+    // switches between lores and hires, resizing and clearing the display
+    pub fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.display = vec![0; self.display_width() * self.display_height()];
+    }
+
+    // the buzzer also sounds while FX0A waits for a held key to be released, like on the COSMAC VIP
+    pub fn is_sounding(&self) -> bool {
+        self.sound_timer > 0 || self.waiting_key.is_some()
+    }
+
+    // the sound the beeper should play while the sound timer is active
+    pub fn sound(&self) -> Sound {
+        match self.audio_pattern {
+            Some(pattern) if self.mode == MachineMode::Xochip => Sound::Pattern { pattern, pitch: self.pitch },
+            _ => Sound::Buzzer,
+        }
+    }
+
+    pub fn load_big_font_set(&mut self, fontset: [u8; 160]) {
+        for (i, font) in fontset.iter().enumerate() {
+            self.memory[BIG_FONTSET_ADDRESS + i] = *font;
+        }
+    }
+
     pub fn load_font_set(&mut self, fontset: [u8; 80]) {
         for (i, font) in fontset.iter().enumerate() {
             self.memory[i] = *font;
@@ -99,303 +337,530 @@ impl Chip8 {
     }
 
     pub fn save_state(&mut self, writer: &mut dyn Write) -> Result<(), SavefileError> {
-        save(writer, 1, self)
+        save(writer, STATE_VERSION, self)
     }
 
     pub fn load_state(&mut self, reader: &mut dyn Read) -> Result<(), SavefileError> {
-        let chip8 = load::<Self>(reader, 1)?;
+        let mut chip8 = load::<Self>(reader, STATE_VERSION)?;
+        // watchpoints are not part of the state, they stay set across loads
+        chip8.watchpoints = std::mem::take(&mut self.watchpoints);
         *self = chip8;
         Ok(())
     }
 
-    pub fn single_cycle(&mut self) {
-        // fetch
-        self.opcode = (self.memory[self.pc as usize] as u16) << 8 | (self.memory[(self.pc + 1) as usize] as u16);
-        self.pc += 2;
-        // decode - none
+    // The machine as json for bug reports, with memory as a hex dump and the display as ascii art.
+    // Loading it back with from_json gives the same machine, watchpoints aside.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        let chip8: Self = serde_json::from_str(json)?;
+        // the sizes are checked here, an edited dump would otherwise crash the emulator later on
+        if chip8.memory.len() != chip8.mode.memory_size() {
+            return Err(serde::de::Error::custom(format!("memory is {:#X} bytes long, {:?} mode has {:#X} bytes", chip8.memory.len(), chip8.mode, chip8.mode.memory_size())));
+        }
+        if chip8.display.len() != chip8.display_width() * chip8.display_height() {
+            return Err(serde::de::Error::custom(format!("the display must be {}x{} pixels", chip8.display_width(), chip8.display_height())));
+        }
+        // and so are the values used as indexes
+        if chip8.stack_pointer as usize > chip8.jump_stack.len() {
+            return Err(serde::de::Error::custom(format!("stack_pointer is {}, the stack has {} entries", chip8.stack_pointer, chip8.jump_stack.len())));
+        }
+        if let Some(key) = chip8.waiting_key.filter(|&key| key as usize >= chip8.key_state.len()) {
+            return Err(serde::de::Error::custom(format!("waiting_key is {}, keys go up to 15", key)));
+        }
+        if chip8.planes > 0b11 {
+            return Err(serde::de::Error::custom(format!("planes is {}, there are only two bitplanes", chip8.planes)));
+        }
+        if let Some(key) = chip8.key_state.iter().position(|&state| state > 1) {
+            return Err(serde::de::Error::custom(format!("key_state of key {:X} is {}, keys are either 0 or 1", key, chip8.key_state[key])));
+        }
+        Ok(chip8)
+    }
+
+    // reads a word without going through the watchpoints, None if it is outside of memory
+    pub fn peek_word(&self, address: u16) -> Option<u16> {
+        let high = *self.memory.get(address as usize)?;
+        let low = *self.memory.get(address as usize + 1)?;
+        Some((high as u16) << 8 | low as u16)
+    }
+
+    // the watched accesses since the last call
+    pub fn take_watch_hits(&mut self) -> Vec<WatchHit> {
+        std::mem::take(&mut self.watch_hits)
+    }
+
+    pub fn single_cycle(&mut self) -> Result<StepOutcome, Chip8Error> {
+        if self.halted {
+            return Ok(StepOutcome::Halted);
+        }
+
+        // fetch, nothing was fetched if it fails so the opcode is reported as 0
+        self.opcode = self.read_word(self.pc).map_err(|_| Chip8Error::MemoryOutOfRange {
+            pc: self.pc,
+            opcode: 0,
+            address: self.pc as usize,
+        })?;
+        self.pc = self.pc.wrapping_add(2);
+        // decode
+        let instruction = decode(self.opcode).ok_or(Chip8Error::UnknownOpcode {
+            pc: self.instruction_pc(),
+            opcode: self.opcode,
+        })?;
         // execute
-        self.execute();
+        self.execute(instruction)
         // store
     }
 
-    fn execute(&mut self) {
-        let x = ((self.opcode & 0x0F00) >> 8) as usize;
-        let y = ((self.opcode & 0x00F0) >> 4) as usize;
-        let nn = (self.opcode & 0x00FF) as u8;
+    fn execute(&mut self, instruction: Instruction) -> Result<StepOutcome, Chip8Error> {
+        // 00DN is a 0NNN outside of XO-CHIP mode, and is ignored like one
+        let instruction = match instruction {
+            Instruction::ScrollUp { .. } if self.mode != MachineMode::Xochip => Instruction::Sys { nnn: self.opcode & 0x0FFF },
+            instruction => instruction,
+        };
+        if instruction.is_xo_chip() && self.mode != MachineMode::Xochip {
+            return Err(Chip8Error::UnknownOpcode { pc: self.instruction_pc(), opcode: self.opcode });
+        }
 
-        match self.opcode  {
-            0x00E0 => {
-                self.display = [0; 2048];
-                return;
+        match instruction {
+            Instruction::Sys { .. } => {
+                // ignored by modern interpreters
+            },
+            Instruction::ClearScreen => {
+                // clears the selected planes
+                let planes = self.planes;
+                self.display.iter_mut().for_each(|pixel| *pixel &= !planes);
             },
-            0x00EE => {
+            Instruction::Return => {
                 // sets pc to the address at the top of the stack
-                self.pc = self.jump_stack[self.stack_pointer as usize];
+                if self.stack_pointer == 0 {
+                    return Err(Chip8Error::StackUnderflow { pc: self.instruction_pc(), opcode: self.opcode });
+                }
                 self.stack_pointer -= 1;
-                return;
+                self.pc = self.jump_stack[self.stack_pointer as usize];
             },
-            _ => {}
-        }
-
-        // matches opcode with last 3 nibbles removed (ie, 0xA22A -> 0xA000)
-        match self.opcode & 0xF000 {
-            0x0000 => {
-                // ignored by modern interpreters
-                return;
+            Instruction::ScrollDown { n } => {
+                self.scroll(0, n as isize);
             },
-            0x1000 => {
-                // 1NNN - jump to address NNN
-                self.pc = self.opcode & 0x0FFF;
-                return;
-            }
-            0x2000 => {
-                // 2NNN - call subroutine at NNN
-                self.stack_pointer += 1;
-                self.jump_stack[self.stack_pointer as usize] = self.pc;
-                self.pc = self.opcode & 0x0FFF;
-                return;
-            }
-            0x3000 => {
-                // 3XNN - skip next instruction if VX == NN
-                if self.v_registers[x] == nn {
-                    self.pc += 2;
-                }
-            }
-            0x4000 => {
-                // 4XNN - skip next instruction if VX != NN
-                if self.v_registers[x] != nn {
-                    self.pc += 2;
-                }
-                return;
-            }
-            0x6000 => {
-                // 6XNN - set VX to NN
-                self.v_registers[x] = nn;
-                return;
-            }
-            0x7000 => {
-                // 7XNN - add NN to VX
-                self.v_registers[x] = (self.v_registers[x] as u16 + nn as u16) as u8;
-                return;
-            },
-            0xA000 => {
-                // ANNN - set I to NNN
-                self.i = self.opcode & 0x0FFF;
-                return;
-            },
-            0xB000 => {
-                // BNNN - jump to address NNN + V0
-                self.pc = (self.opcode & 0x0FFF) + self.v_registers[0] as u16;
-                return;
-            },
-            0xC000 => {
-                // CXNN - set VX to random byte ANDed with NN
-                self.v_registers[x] = nn & rand::thread_rng().gen::<u8>();
-                return;
-            },
-            0xD000 => {
-                // DXYN - draw sprite at VX, VY with N bytes of sprite data starting at I
-                let width = 8;
-                let nbytes = self.opcode & 0x000F;
-
-                // vregisters at x and y
-                let vx = self.v_registers[x];
-                let vy = self.v_registers[y];
-
-                // set last register to 0
-                self.v_registers[0xF] = 0;
-
-                for row in 0..nbytes {
-                    // get the sprite from memory
-                    let mut sprt = self.memory[(self.i + row) as usize];
-
-                    for col in 0..width {
-                        // if the sprite is not 0
-                        if sprt & 0x0080 > 0 {
-                            let disppixel = &mut self.display[(
-                                ((vy as u16 + row) % 32) * 64 +
-                                (vx as u16 + col) % 64
-                            ) as usize];
-
-                            // set last register to 1 if pixel is set
-                            if *disppixel == 1 {
-                                self.v_registers[0xF] = 1;
-                            }
-
-                            // toggle pixel
-                            *disppixel ^= 1;
-                        }
-
-                        // shift the sprite to the right to be ready for next draw
-                        sprt <<= 1;
-                    }
+            Instruction::ScrollUp { n } => {
+                self.scroll(0, -(n as isize));
+            },
+            Instruction::ScrollRight => {
+                self.scroll(4, 0);
+            },
+            Instruction::ScrollLeft => {
+                self.scroll(-4, 0);
+            },
+            Instruction::Exit => {
+                self.halted = true;
+                return Ok(StepOutcome::Halted);
+            },
+            Instruction::LowRes => {
+                self.set_hires(false);
+            },
+            Instruction::HighRes => {
+                self.set_hires(true);
+            },
+            Instruction::Jump { nnn } => {
+                self.pc = nnn;
+            },
+            Instruction::Call { nnn } => {
+                if self.stack_pointer as usize >= self.jump_stack.len() {
+                    return Err(Chip8Error::StackOverflow { pc: self.instruction_pc(), opcode: self.opcode });
                 }
-                return;
+                self.jump_stack[self.stack_pointer as usize] = self.pc;
+                self.stack_pointer += 1;
+                self.pc = nnn;
             },
-            _ => {}
-        }
-
-        // matches first and last nibbles (ie, 0xA22A -> 0xA00A) (mostly alu v-register operations)
-        match self.opcode & 0xF00F {
-            0x5000 => {
-                // 5XY0 - skip next instruction if VX == VY
-                if self.v_registers[x] == self.v_registers[y] {
-                    self.pc += 2;
+            Instruction::SkipEqImm { x, nn } => {
+                self.skip_if(self.v_registers[x as usize] == nn)?;
+            },
+            Instruction::SkipNeImm { x, nn } => {
+                self.skip_if(self.v_registers[x as usize] != nn)?;
+            },
+            Instruction::SkipEqReg { x, y } => {
+                self.skip_if(self.v_registers[x as usize] == self.v_registers[y as usize])?;
+            },
+            Instruction::SaveRange { x, y } => {
+                // I is unchanged, registers are stored in descending order if X > Y
+                let (x, y) = (x as usize, y as usize);
+                self.check_memory(self.i as usize, x.abs_diff(y) + 1)?;
+                for (offset, index) in Self::register_range(x, y).enumerate() {
+                    self.write_memory(self.i as usize + offset, self.v_registers[index]);
                 }
-                return;
-            },
-            0x8000 => {
-                // 8XY0 - set VX to VY
-                self.v_registers[x] = self.v_registers[y];
-                return;
-            },
-            0x8001 => {
-                // 8XY1 - set VX to VX | VY
-                self.v_registers[x] |= self.v_registers[y];
-                return;
-            },
-            0x8002 => {
-                // 8XY2 - set VX to VX & VY
-                self.v_registers[x] &= self.v_registers[y];
-                return;
-            },
-            0x8003 => {
-                // 8XY3 - set VX to VX ^ VY
-                self.v_registers[x] ^= self.v_registers[y];
-                return;
-            },
-            0x8004 => {
-                // 8XY4 - set VF to 1 if carry, set VX to VX + VY
-
-                // Checks if the hex nibbles plussed together uses more than 8 bits, meaning it has carried over.
-                let result = self.v_registers[x] as u16 + self.v_registers[y] as u16;
-                if result > 0x00FF {
-                    self.v_registers[0xF] = 1;
+            },
+            Instruction::LoadRange { x, y } => {
+                // I is unchanged, registers are loaded in descending order if X > Y
+                let (x, y) = (x as usize, y as usize);
+                self.check_memory(self.i as usize, x.abs_diff(y) + 1)?;
+                for (offset, index) in Self::register_range(x, y).enumerate() {
+                    self.v_registers[index] = self.read_memory(self.i as usize + offset);
                 }
-                else {
+            },
+            Instruction::LoadImm { x, nn } => {
+                self.v_registers[x as usize] = nn;
+            },
+            Instruction::AddImm { x, nn } => {
+                // VF is not affected by the carry
+                self.v_registers[x as usize] = self.v_registers[x as usize].wrapping_add(nn);
+            },
+            Instruction::Move { x, y } => {
+                self.v_registers[x as usize] = self.v_registers[y as usize];
+            },
+            Instruction::Or { x, y } => {
+                self.v_registers[x as usize] |= self.v_registers[y as usize];
+                if self.quirks.vf_reset {
                     self.v_registers[0xF] = 0;
                 }
-                self.v_registers[x] = self.v_registers[x].wrapping_add(self.v_registers[y]);
-                return;
             },
-            0x8005 => {
-                // 8XY5 - set VF to 0 if borrow, set VX to VX - VY
-                if self.v_registers[x] > self.v_registers[y] {
-                    self.v_registers[0xF] = 1;
-                }
-                else {
+            Instruction::And { x, y } => {
+                self.v_registers[x as usize] &= self.v_registers[y as usize];
+                if self.quirks.vf_reset {
                     self.v_registers[0xF] = 0;
                 }
-
-                self.v_registers[x] = self.v_registers[x].wrapping_sub(self.v_registers[y]);
-                return;
             },
-            0x8006 => {
-                // 8XY6 - set VF to LSB of VX, set VX to VX >> 1
-
-                // Set VF to least significant bit of VX
-                self.v_registers[0xF] = self.v_registers[x] & 0x01;
-
-                self.v_registers[x] >>= 1;
-
-                return;
-            },
-            0x8007 => {
-                // 8XY7 - set VX to VY - VX, set VF to 0 if borrow
-                if self.v_registers[y] > self.v_registers[x] {
-                    self.v_registers[0xF] = 1;
-                }
-                else {
+            Instruction::Xor { x, y } => {
+                self.v_registers[x as usize] ^= self.v_registers[y as usize];
+                if self.quirks.vf_reset {
                     self.v_registers[0xF] = 0;
                 }
-
-                self.v_registers[x] -= self.v_registers[y];
-                return;
             },
-            0x800E => {
-                // 8XYE - set VX to VX << 1, set VF to MSB of VX
-
-                // set registers by pushing unneeded bits off, and leaving with the MSB
-                self.v_registers[0xF] = self.v_registers[x] >> 7;
+            // The flag is written after the result, so it wins when X is F. Both are computed from the
+            // operands before either is written, as Y can be F too.
+            Instruction::Add { x, y } => {
+                let (x, y) = (x as usize, y as usize);
+                let (result, carry) = self.v_registers[x].overflowing_add(self.v_registers[y]);
+                self.v_registers[x] = result;
+                self.v_registers[0xF] = carry as u8;
+            },
+            Instruction::Sub { x, y } => {
+                let (x, y) = (x as usize, y as usize);
+                // VF is 1 when there is no borrow, including when VX equals VY
+                let (result, borrow) = self.v_registers[x].overflowing_sub(self.v_registers[y]);
+                self.v_registers[x] = result;
+                self.v_registers[0xF] = !borrow as u8;
+            },
+            Instruction::ShiftRight { x, y } => {
+                // with the shifting quirk, VX is shifted in place and VY is ignored
+                let source = if self.quirks.shifting { self.v_registers[x as usize] } else { self.v_registers[y as usize] };
 
-                self.v_registers[x] <<= 1;
-                return;
+                self.v_registers[x as usize] = source >> 1;
+                // VF is the bit that was shifted out
+                self.v_registers[0xF] = source & 0x01;
             },
-            0x9000 => {
-                // 9XY0 - skip next instruction if VX != VY
-                if self.v_registers[x] != self.v_registers[y] {
-                    self.pc += 2;
-                }
-                return;
+            Instruction::SubReverse { x, y } => {
+                let (x, y) = (x as usize, y as usize);
+                let (result, borrow) = self.v_registers[y].overflowing_sub(self.v_registers[x]);
+                self.v_registers[x] = result;
+                self.v_registers[0xF] = !borrow as u8;
             },
-            _ => {}
-        }
+            Instruction::ShiftLeft { x, y } => {
+                // with the shifting quirk, VX is shifted in place and VY is ignored
+                let source = if self.quirks.shifting { self.v_registers[x as usize] } else { self.v_registers[y as usize] };
 
-        match self.opcode & 0xF0FF {
-            0xE09E => {
-                // EX9E - skip next instruction if key in VX is pressed
-                if self.key_state[self.v_registers[x] as usize] != 0 {
-                    self.pc += 2;
+                self.v_registers[x as usize] = source << 1;
+                // VF is the bit that was shifted out
+                self.v_registers[0xF] = source >> 7;
+            },
+            Instruction::SkipNeReg { x, y } => {
+                self.skip_if(self.v_registers[x as usize] != self.v_registers[y as usize])?;
+            },
+            Instruction::LoadIndex { nnn } => {
+                self.set_index(nnn);
+            },
+            Instruction::JumpOffset { x, nnn } => {
+                // with the jumping quirk, BXNN - jump to address XNN + VX
+                let offset = if self.quirks.jumping { self.v_registers[x as usize] } else { self.v_registers[0] };
+                self.pc = nnn + offset as u16;
+            },
+            Instruction::Random { x, nn } => {
+                self.v_registers[x as usize] = nn & self.rng.next_u8();
+            },
+            Instruction::Draw { x, y, n } => {
+                // DXY0 draws a 16x16 sprite with 32 bytes of sprite data
+                let (width, height) = if n == 0 { (16, 16) } else { (8, n as usize) };
+
+                let vx = self.v_registers[x as usize] as usize;
+                let vy = self.v_registers[y as usize] as usize;
+                self.draw_sprite(vx, vy, width, height)?;
+                if self.quirks.display_wait {
+                    return Ok(StepOutcome::Drew);
                 }
             },
-            0xE0A1 => {
-                // EXA1 - skip next instruction if key in VX is not pressed
-                if self.key_state[self.v_registers[x] as usize] == 0 {
-                    self.pc += 2;
+            Instruction::SkipKeyPressed { x } => {
+                self.skip_if(self.key_state[(self.v_registers[x as usize] & 0x0F) as usize] != 0)?;
+            },
+            Instruction::SkipKeyNotPressed { x } => {
+                self.skip_if(self.key_state[(self.v_registers[x as usize] & 0x0F) as usize] == 0)?;
+            },
+            Instruction::LoadLongIndex => {
+                // the address is stored in the word after the opcode
+                let address = self.read_word(self.pc)?;
+                self.set_index(address);
+                self.pc = self.pc.wrapping_add(2);
+            },
+            Instruction::SelectPlanes { n } => {
+                self.planes = n & 0b11;
+            },
+            Instruction::LoadAudioPattern => {
+                self.check_memory(self.i as usize, 16)?;
+                let mut pattern = [0; 16];
+                for (offset, byte) in pattern.iter_mut().enumerate() {
+                    *byte = self.read_memory(self.i as usize + offset);
                 }
+                self.audio_pattern = Some(pattern);
             },
-            0xF007 => {
-                // FX07 - set VX to delay timer value
-                self.v_registers[x] = self.delay_timer;
-            },
-            0xF00A => {
-                // FX0A - wait for keypress, store in VX
-                // todo: maybe broken!
-                match self.key_state.iter().position(|&x| x != 0) {
-                    Some(key) => {
-                        self.v_registers[x] = key as u8;
-                    },
-                    None => {
-                        self.pc -= 2;
+            Instruction::LoadDelay { x } => {
+                self.v_registers[x as usize] = self.delay_timer;
+            },
+            Instruction::WaitKey { x } => {
+                let pressed = self.key_state.iter().position(|&key| key != 0).map(|key| key as u8);
+                if self.quirks.key_wait_on_press {
+                    match pressed {
+                        Some(key) => self.v_registers[x as usize] = key,
+                        None => self.pc = self.pc.wrapping_sub(2),
+                    }
+                }
+                else {
+                    // the first key pressed is returned once it is released
+                    match self.waiting_key.or(pressed) {
+                        Some(key) if self.key_state[key as usize] == 0 => {
+                            self.v_registers[x as usize] = key;
+                            self.waiting_key = None;
+                        },
+                        key => {
+                            self.waiting_key = key;
+                            self.pc = self.pc.wrapping_sub(2);
+                        },
                     }
                 }
             },
-            0xF015 => {
-                // FX15 - set delay timer to VX
-                self.delay_timer = self.v_registers[x];
+            Instruction::SetDelay { x } => {
+                self.delay_timer = self.v_registers[x as usize];
             },
-            0xF018 => {
-                // FX18 - set sound timer to VX
-                self.sound_timer = self.v_registers[x];
+            Instruction::SetSound { x } => {
+                self.sound_timer = self.v_registers[x as usize];
             },
-            0xF01E => {
-                // FX1E - add VX to I, set to I
-                self.i += self.v_registers[x] as u16;
+            Instruction::AddIndex { x } => {
+                self.set_index(self.i.wrapping_add(self.v_registers[x as usize] as u16));
             },
-            0xF029 => {
-                // FX29 - set I to location of sprite for digit VX
+            Instruction::LoadFont { x } => {
                 // multiplied by 5, as each sprite is 5 bytes long
-                self.i = self.v_registers[x] as u16 * 5;
-            },
-            0xF033 => {
-                // FX33 - store BCD representation of VX in memory locations I, I+1, and I+2
-                self.memory[self.i as usize] = (self.v_registers[x] / 100) % 10;
-                self.memory[(self.i + 1) as usize] = (self.v_registers[x] / 10) % 10;
-                self.memory[(self.i + 2) as usize] = self.v_registers[x] % 10;
-            },
-            0xF055 => {
-                // FX55 - store V0 to VX in memory starting at address I
-                for index in 0..x {
-                    self.memory[self.i as usize + index] = self.v_registers[index];
+                self.i = self.v_registers[x as usize] as u16 * 5;
+            },
+            Instruction::LoadBigFont { x } => {
+                // multiplied by 10, as each big sprite is 10 bytes long
+                self.i = BIG_FONTSET_ADDRESS as u16 + (self.v_registers[x as usize] & 0x0F) as u16 * 10;
+            },
+            Instruction::StoreBcd { x } => {
+                let vx = self.v_registers[x as usize];
+                self.check_memory(self.i as usize, 3)?;
+                self.write_memory(self.i as usize, (vx / 100) % 10);
+                self.write_memory(self.i as usize + 1, (vx / 10) % 10);
+                self.write_memory(self.i as usize + 2, vx % 10);
+            },
+            Instruction::SetPitch { x } => {
+                self.pitch = self.v_registers[x as usize];
+            },
+            Instruction::StoreRegisters { x } => {
+                let x = x as usize;
+                // V0 to VX inclusive
+                self.check_memory(self.i as usize, x + 1)?;
+                for index in 0..=x {
+                    self.write_memory(self.i as usize + index, self.v_registers[index]);
                 }
+                self.increment_index_after_load_store(x);
             },
-            0xF065 => {
-                // FX65 - read V0 to VX from memory starting at address I
-                for index in 0..x {
-                    self.v_registers[index] = self.memory[self.i as usize + index];
+            Instruction::LoadRegisters { x } => {
+                let x = x as usize;
+                self.check_memory(self.i as usize, x + 1)?;
+                for index in 0..=x {
+                    self.v_registers[index] = self.read_memory(self.i as usize + index);
                 }
+                self.increment_index_after_load_store(x);
             },
-            _ => {}
+            Instruction::StoreFlags { x } => {
+                let x = x as usize;
+                self.rpl_flags[..=x].copy_from_slice(&self.v_registers[..=x]);
+            },
+            Instruction::LoadFlags { x } => {
+                let x = x as usize;
+                self.v_registers[..=x].copy_from_slice(&self.rpl_flags[..=x]);
+            },
+        }
+        Ok(StepOutcome::Executed)
+    }
+
+    fn draw_sprite(&mut self, vx: usize, vy: usize, width: usize, height: usize) -> Result<(), Chip8Error> {
+        let display_width = self.display_width();
+        let display_height = self.display_height();
+        let bytes_per_row = width / 8;
+        let planes = (self.planes & 0b01 != 0) as usize + (self.planes & 0b10 != 0) as usize;
+        self.check_memory(self.i as usize, planes * height * bytes_per_row)?;
+
+        // the starting position always wraps around the screen
+        let vx = vx % display_width;
+        let vy = vy % display_height;
+
+        // set last register to 0
+        self.v_registers[0xF] = 0;
+
+        // each selected plane is drawn with its own sprite, one after another in memory
+        let mut address = self.i as usize;
+        for plane in [0b01, 0b10] {
+            if self.planes & plane == 0 {
+                continue;
+            }
+
+            for row in 0..height {
+                // with the clipping quirk, rows past the bottom edge are not drawn
+                if self.quirks.clipping && vy + row >= display_height {
+                    break;
+                }
+
+                // the sprite bytes for this row, 2 for 16x16 sprites
+                let mut sprite_row = [0; 2];
+                for (col_byte, byte) in sprite_row.iter_mut().enumerate().take(bytes_per_row) {
+                    *byte = self.read_memory(address + row * bytes_per_row + col_byte);
+                }
+
+                for col in 0..width {
+                    // with the clipping quirk, columns past the right edge are not drawn
+                    if self.quirks.clipping && vx + col >= display_width {
+                        break;
+                    }
+
+                    // check the bit of the sprite byte for this column
+                    let sprt = sprite_row[col / 8];
+                    if sprt & (0x80 >> (col % 8)) > 0 {
+                        let disppixel = &mut self.display[
+                            ((vy + row) % display_height) * display_width +
+                            (vx + col) % display_width
+                        ];
+
+                        // set last register to 1 if pixel is set
+                        if *disppixel & plane != 0 {
+                            self.v_registers[0xF] = 1;
+                        }
+
+                        // toggle pixel
+                        *disppixel ^= plane;
+                    }
+                }
+            }
+            address += height * bytes_per_row;
+        }
+        Ok(())
+    }
+
+    // scrolls the selected planes by dx pixels right and dy pixels down
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let width = self.display_width() as isize;
+        let height = self.display_height() as isize;
+        let planes = self.planes;
+        let previous = self.display.clone();
+
+        for y in 0..height {
+            for x in 0..width {
+                let (src_x, src_y) = (x - dx, y - dy);
+                let src = if (0..width).contains(&src_x) && (0..height).contains(&src_y) {
+                    previous[(src_y * width + src_x) as usize]
+                }
+                else {
+                    0
+                };
+                let pixel = &mut self.display[(y * width + x) as usize];
+                *pixel = (*pixel & !planes) | (src & planes);
+            }
+        }
+    }
+
+    fn skip_if(&mut self, condition: bool) -> Result<(), Chip8Error> {
+        if !condition {
+            return Ok(());
+        }
+
+        // F000 NNNN is the only 4 byte long instruction, and is skipped over entirely in XO-CHIP mode.
+        // the next instruction is only read for it, the other modes skip past the end of memory like before
+        let next_size = match self.mode {
+            MachineMode::Xochip => decode(self.read_word(self.pc)?).map_or(2, |next| next.size()),
+            MachineMode::Chip8 => 2,
+        };
+        self.pc = self.pc.wrapping_add(next_size);
+        Ok(())
+    }
+
+    // address of the instruction currently being executed, pc has already moved past it
+    fn instruction_pc(&self) -> u16 {
+        self.pc.wrapping_sub(2)
+    }
+
+    fn read_word(&self, address: u16) -> Result<u16, Chip8Error> {
+        self.check_memory(address as usize, 2)?;
+        Ok((self.memory[address as usize] as u16) << 8 | (self.memory[address as usize + 1] as u16))
+    }
+
+    // memory accesses of instructions go through these, so watchpoints can observe them.
+    // the address has to be checked with check_memory first
+    fn read_memory(&mut self, address: usize) -> u8 {
+        let value = self.memory[address];
+        if !self.watchpoints.is_empty() && is_watched(&self.watchpoints, address, Access::Read) {
+            self.record_watch_hit(Access::Read, address as u16, value as u16, value as u16);
+        }
+        value
+    }
+
+    fn write_memory(&mut self, address: usize, value: u8) {
+        let old = self.memory[address];
+        self.memory[address] = value;
+        if !self.watchpoints.is_empty() && is_watched(&self.watchpoints, address, Access::Write) {
+            self.record_watch_hit(Access::Write, address as u16, old as u16, value as u16);
+        }
+    }
+
+    fn set_index(&mut self, address: u16) {
+        let old = self.i;
+        self.i = address;
+        if !self.watchpoints.is_empty() && is_watched(&self.watchpoints, address as usize, Access::Index) {
+            self.record_watch_hit(Access::Index, address, old, address);
+        }
+    }
+
+    fn record_watch_hit(&mut self, access: Access, address: u16, old: u16, new: u16) {
+        self.watch_hits.push(WatchHit {
+            pc: self.instruction_pc(),
+            opcode: self.opcode,
+            access,
+            address,
+            old,
+            new,
+        });
+    }
+
+    // errors if any of the len bytes starting at address are past the end of memory
+    fn check_memory(&self, address: usize, len: usize) -> Result<(), Chip8Error> {
+        if address + len > self.memory.len() {
+            return Err(Chip8Error::MemoryOutOfRange { pc: self.instruction_pc(), opcode: self.opcode, address: address + len - 1 });
+        }
+        Ok(())
+    }
+
+    // registers from X to Y, in descending order if X > Y
+    fn register_range(x: usize, y: usize) -> Box<dyn Iterator<Item = usize>> {
+        if x <= y {
+            Box::new(x..=y)
+        }
+        else {
+            Box::new((y..=x).rev())
+        }
+    }
+
+    fn increment_index_after_load_store(&mut self, x: usize) {
+        match self.quirks.load_store {
+            LoadStoreQuirk::Unchanged => {},
+            LoadStoreQuirk::Increment => self.i = self.i.wrapping_add(x as u16 + 1),
+            LoadStoreQuirk::IncrementByX => self.i = self.i.wrapping_add(x as u16),
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,627 @@
+use std::collections::{BTreeSet, HashSet, VecDeque};
+use std::fmt::{self, Display, Write};
+use std::str::FromStr;
+
+use crate::chip8::{Chip8, StepOutcome};
+use crate::disasm::{mnemonic, Syntax};
+use crate::errors::Chip8Error;
+use crate::instruction::decode;
+use crate::trace::Tracer;
+use crate::watchpoint::{WatchHit, WatchKind, Watchpoint};
+
+pub const HELP: &str = "\
+Addresses, lengths and values are hex, with or without a 0x prefix, counts are decimal.
+
+step [count]          (s)  execute count instructions, 1 by default
+continue              (c)  run until a breakpoint is hit, enter pauses
+step-back [count]     (sb) go back count instructions, 1 by default
+reverse-continue      (rc) go back to the previous breakpoint or watchpoint hit
+break <address>       (b)  break when pc reaches address
+breakop <pattern>          break before an opcode matching pattern, e.g. DXYN or F.33
+delete <address>           delete the breakpoint at address
+deleteop <pattern>         delete an opcode breakpoint
+watch <start> [end] [r|w|rw]
+                      (w)  break when memory from start to end is read or written by
+                           DXYN, FX33, FX55, FX65 and the like, or ANNN and FX1E point I at it
+unwatch <start>            delete the watchpoints starting at start
+breakpoints                list the breakpoints and watchpoints
+registers             (r)  print the registers
+stack                      print the stack
+mem <address> [len]   (x)  dump len bytes of memory, 0x40 by default
+set <register> <value>     set V0-VF, I, PC, DT or ST
+help                  (h)  print this help
+quit                  (q)  exit the emulator
+";
+
+// An opcode with wildcards, hex digits have to match and every other character matches any nibble.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct OpcodePattern {
+    value: u16,
+    mask: u16,
+}
+
+impl OpcodePattern {
+    pub fn matches(&self, opcode: u16) -> bool {
+        opcode & self.mask == self.value
+    }
+}
+
+impl FromStr for OpcodePattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.chars().count() != 4 {
+            return Err(format!("Opcode pattern {} is not 4 characters long", s));
+        }
+        let mut pattern = Self { value: 0, mask: 0 };
+        for c in s.chars() {
+            pattern.value <<= 4;
+            pattern.mask <<= 4;
+            if let Some(digit) = c.to_digit(16) {
+                pattern.value |= digit as u16;
+                pattern.mask |= 0xF;
+            }
+        }
+        Ok(pattern)
+    }
+}
+
+impl Display for OpcodePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for shift in [12, 8, 4, 0] {
+            if (self.mask >> shift) & 0xF == 0 {
+                write!(f, ".")?;
+            }
+            else {
+                write!(f, "{:X}", (self.value >> shift) & 0xF)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Register {
+    V(u8),
+    I,
+    Pc,
+    DelayTimer,
+    SoundTimer,
+}
+
+impl FromStr for Register {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let upper = s.to_uppercase();
+        match upper.as_str() {
+            "I" => Ok(Self::I),
+            "PC" => Ok(Self::Pc),
+            "DT" => Ok(Self::DelayTimer),
+            "ST" => Ok(Self::SoundTimer),
+            _ => match upper.strip_prefix('V').map(|x| u8::from_str_radix(x, 16)) {
+                Some(Ok(x)) if x < 16 => Ok(Self::V(x)),
+                _ => Err(format!("Unknown register: {}", s)),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    Step(u32),
+    Continue,
+    StepBack(u32),
+    ReverseContinue,
+    Break(u16),
+    BreakOpcode(OpcodePattern),
+    Delete(u16),
+    DeleteOpcode(OpcodePattern),
+    Watch(Watchpoint),
+    Unwatch(u16),
+    Breakpoints,
+    Registers,
+    Stack,
+    Memory { address: u16, len: u16 },
+    Set { register: Register, value: u16 },
+    Help,
+    Quit,
+}
+
+fn parse_number(s: &str) -> Result<u16, String> {
+    let digits = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")).unwrap_or(s);
+    u16::from_str_radix(digits, 16).map_err(|_| format!("Invalid number: {}", s))
+}
+
+// 1 if no count is given
+fn parse_count(s: Option<&&str>) -> Result<u32, String> {
+    s.map_or(Ok(1), |count| count.parse::<u32>().map_err(|_| format!("Invalid count: {}", count)))
+}
+
+// <start> [end] [r|w|rw]
+fn parse_watchpoint(arguments: &[&str]) -> Result<Watchpoint, String> {
+    let mut arguments = arguments.iter().copied().peekable();
+    let start = parse_number(arguments.next().ok_or("watch is missing an argument")?)?;
+    let end = match arguments.peek() {
+        Some(end) if parse_number(end).is_ok() => parse_number(arguments.next().unwrap())?,
+        _ => start,
+    };
+    let kind = match arguments.next().map(|kind| kind.to_lowercase()).as_deref() {
+        None | Some("rw") => WatchKind::ReadWrite,
+        Some("r") => WatchKind::Read,
+        Some("w") => WatchKind::Write,
+        Some(kind) => return Err(format!("Unknown watch kind: {}, expected r, w or rw", kind)),
+    };
+    if end < start {
+        return Err(format!("Watch range {:#05X}-{:#05X} ends before it starts", start, end));
+    }
+    Ok(Watchpoint { start, end, kind })
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words = s.split_whitespace().collect::<Vec<_>>();
+        let argument = |index: usize| words.get(index).copied().ok_or_else(|| format!("{} is missing an argument", words[0]));
+
+        let Some(name) = words.first() else {
+            return Err("No command given".to_string());
+        };
+        let command = match name.to_lowercase().as_str() {
+            "step" | "s" => Command::Step(parse_count(words.get(1))?),
+            "continue" | "c" => Command::Continue,
+            "step-back" | "sb" => Command::StepBack(parse_count(words.get(1))?),
+            "reverse-continue" | "rc" => Command::ReverseContinue,
+            "break" | "b" => Command::Break(parse_number(argument(1)?)?),
+            "breakop" => Command::BreakOpcode(argument(1)?.parse()?),
+            "delete" => Command::Delete(parse_number(argument(1)?)?),
+            "deleteop" => Command::DeleteOpcode(argument(1)?.parse()?),
+            "watch" | "w" => Command::Watch(parse_watchpoint(&words[1..])?),
+            "unwatch" => Command::Unwatch(parse_number(argument(1)?)?),
+            "breakpoints" => Command::Breakpoints,
+            "registers" | "r" => Command::Registers,
+            "stack" => Command::Stack,
+            "mem" | "x" => Command::Memory {
+                address: parse_number(argument(1)?)?,
+                len: words.get(2).map_or(Ok(0x40), |len| parse_number(len))?,
+            },
+            "set" => Command::Set {
+                register: argument(1)?.parse()?,
+                value: parse_number(argument(2)?)?,
+            },
+            "help" | "h" => Command::Help,
+            "quit" | "q" => Command::Quit,
+            _ => return Err(format!("Unknown command: {}, try help", name)),
+        };
+        Ok(command)
+    }
+}
+
+// Why execution stopped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    Breakpoint { pc: u16 },
+    OpcodeBreakpoint { pc: u16, opcode: u16, pattern: OpcodePattern },
+    // every watched access of the instruction that was just executed
+    Watchpoint(Vec<WatchHit>),
+    Halted { pc: u16 },
+    Crashed(Chip8Error),
+    // the trace could not be written, tracing is stopped
+    TraceFailed(String),
+}
+
+impl Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::Breakpoint { pc } => write!(f, "Breakpoint at {:#05X}", pc),
+            StopReason::OpcodeBreakpoint { pc, opcode, pattern } => write!(f, "Opcode {:04X} matches {} at {:#05X}", opcode, pattern, pc),
+            StopReason::Watchpoint(hits) => {
+                let hits = hits.iter().map(|hit| hit.to_string()).collect::<Vec<_>>();
+                write!(f, "{}", hits.join("\n"))
+            },
+            StopReason::Halted { pc } => write!(f, "Halted at {:#05X}", pc),
+            StopReason::Crashed(err) => write!(f, "Crashed: {}", err),
+            StopReason::TraceFailed(err) => write!(f, "Tracing stopped: {}", err),
+        }
+    }
+}
+
+// cycles between the snapshots that reverse execution starts from
+const SNAPSHOT_INTERVAL: u64 = 1000;
+// the oldest snapshots are dropped past this, which is about 4 minutes of history at 500 Hz
+const MAX_SNAPSHOTS: usize = 128;
+
+struct Snapshot {
+    cycle: u64,
+    // serialized with Chip8::save_state
+    state: Vec<u8>,
+}
+
+// Drives a Chip8 one instruction at a time, the frontend feeds it commands and calls `cycle`
+// at its own pace while it is running.
+//
+// Going backwards restores the last snapshot before the target cycle and executes forward from it.
+// This is exact because everything an instruction depends on is either in the snapshot, including
+// the CXNN random number generator, or recorded: the timers tick on a cycle count and the key
+// states are kept on a timeline. Registers changed with set are not replayed, the state after
+// the change is taken as a snapshot instead.
+pub struct Debugger {
+    breakpoints: BTreeSet<u16>,
+    opcode_breakpoints: HashSet<OpcodePattern>,
+    // the timers are decremented once every cycles_per_timer_tick instructions
+    cycles_per_timer_tick: u64,
+    // instructions executed since the debugger started
+    cycle: u64,
+    snapshots: VecDeque<Snapshot>,
+    // the key states from the given cycle on, a new entry every time they change
+    inputs: Vec<(u64, [u8; 16])>,
+    running: bool,
+    // breakpoints at this pc are ignored once, so continuing from a breakpoint does not stop right away
+    resumed_at: Option<u16>,
+    tracer: Option<Tracer>,
+}
+
+impl Debugger {
+    pub fn new(cycles_per_timer_tick: u64) -> Self {
+        Self {
+            breakpoints: BTreeSet::new(),
+            opcode_breakpoints: HashSet::new(),
+            cycles_per_timer_tick: cycles_per_timer_tick.max(1),
+            cycle: 0,
+            snapshots: VecDeque::new(),
+            inputs: Vec::new(),
+            running: false,
+            resumed_at: None,
+            tracer: None,
+        }
+    }
+
+    // every instruction the debugger executes is recorded to the tracer
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = Some(tracer);
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    pub fn pause(&mut self) {
+        self.running = false;
+        self.flush_trace();
+    }
+
+    fn flush_trace(&mut self) {
+        // a failed flush shows up as an error on the next record
+        if let Some(tracer) = &mut self.tracer {
+            tracer.flush().ok();
+        }
+    }
+
+    // Runs a command and returns what should be printed. Quit is left to the frontend.
+    pub fn execute(&mut self, chip8: &mut Chip8, command: Command) -> String {
+        let mut out = String::new();
+        match command {
+            Command::Step(count) => {
+                self.resumed_at = Some(chip8.pc);
+                for _ in 0..count {
+                    if let Some(reason) = self.cycle(chip8) {
+                        writeln!(out, "{}", reason).unwrap();
+                        break;
+                    }
+                }
+                self.pause();
+                writeln!(out, "{}", location(chip8)).unwrap();
+            },
+            Command::Continue => {
+                self.resumed_at = Some(chip8.pc);
+                self.running = true;
+            },
+            Command::StepBack(count) => {
+                let target = self.cycle.saturating_sub(count as u64);
+                if !self.restore(chip8, target) {
+                    writeln!(out, "No history to go back to").unwrap();
+                }
+                else if self.cycle > target {
+                    writeln!(out, "Reached the start of the history").unwrap();
+                }
+                writeln!(out, "{}", location(chip8)).unwrap();
+            },
+            Command::ReverseContinue => {
+                match self.reverse_continue(chip8) {
+                    Some(reason) => writeln!(out, "{}", reason).unwrap(),
+                    None if self.snapshots.is_empty() => writeln!(out, "No history to go back to").unwrap(),
+                    None => writeln!(out, "Reached the start of the history").unwrap(),
+                }
+                writeln!(out, "{}", location(chip8)).unwrap();
+            },
+            Command::Break(address) => {
+                self.breakpoints.insert(address);
+            },
+            Command::BreakOpcode(pattern) => {
+                self.opcode_breakpoints.insert(pattern);
+            },
+            Command::Delete(address) => {
+                if !self.breakpoints.remove(&address) {
+                    writeln!(out, "No breakpoint at {:#05X}", address).unwrap();
+                }
+            },
+            Command::DeleteOpcode(pattern) => {
+                if !self.opcode_breakpoints.remove(&pattern) {
+                    writeln!(out, "No opcode breakpoint {}", pattern).unwrap();
+                }
+            },
+            Command::Watch(watchpoint) => {
+                chip8.watchpoints.push(watchpoint);
+            },
+            Command::Unwatch(start) => {
+                let count = chip8.watchpoints.len();
+                chip8.watchpoints.retain(|watchpoint| watchpoint.start != start);
+                if chip8.watchpoints.len() == count {
+                    writeln!(out, "No watchpoint at {:#05X}", start).unwrap();
+                }
+            },
+            Command::Breakpoints => {
+                for address in &self.breakpoints {
+                    writeln!(out, "{:#05X}", address).unwrap();
+                }
+                for pattern in &self.opcode_breakpoints {
+                    writeln!(out, "{}", pattern).unwrap();
+                }
+                for watchpoint in &chip8.watchpoints {
+                    let kind = match watchpoint.kind {
+                        WatchKind::Read => "r",
+                        WatchKind::Write => "w",
+                        WatchKind::ReadWrite => "rw",
+                    };
+                    writeln!(out, "watch {:#05X}-{:#05X} {}", watchpoint.start, watchpoint.end, kind).unwrap();
+                }
+            },
+            Command::Registers => {
+                for (row, registers) in chip8.v_registers.chunks(8).enumerate() {
+                    let line = registers.iter().enumerate()
+                        .map(|(x, value)| format!("V{:X} {:02X}", row * 8 + x, value))
+                        .collect::<Vec<_>>()
+                        .join("  ");
+                    writeln!(out, "{}", line).unwrap();
+                }
+                writeln!(
+                    out,
+                    "I {:04X}  PC {:04X}  DT {:02X}  ST {:02X}  SP {:X}  cycle {}",
+                    chip8.i, chip8.pc, chip8.delay_timer, chip8.sound_timer, chip8.stack_pointer, self.cycle
+                ).unwrap();
+            },
+            Command::Stack => {
+                if chip8.stack_pointer == 0 {
+                    writeln!(out, "Stack is empty").unwrap();
+                }
+                // the top of the stack first
+                for level in (0..chip8.stack_pointer as usize).rev() {
+                    writeln!(out, "{:X}: {:#05X}", level, chip8.jump_stack[level]).unwrap();
+                }
+            },
+            Command::Memory { address, len } => {
+                let start = address as usize;
+                if start >= chip8.memory.len() {
+                    writeln!(out, "{:#05X} is outside of memory", address).unwrap();
+                }
+                let end = (start + len as usize).min(chip8.memory.len());
+                for row_start in (start..end).step_by(16) {
+                    let row = &chip8.memory[row_start..(row_start + 16).min(end)];
+                    let bytes = row.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<_>>().join(" ");
+                    writeln!(out, "{:04X}  {}", row_start, bytes).unwrap();
+                }
+            },
+            Command::Set { register, value } => {
+                match set_register(chip8, register, value) {
+                    Ok(()) => self.take_edit_snapshot(chip8),
+                    Err(err) => writeln!(out, "{}", err).unwrap(),
+                }
+            },
+            Command::Help => {
+                out.push_str(HELP);
+            },
+            Command::Quit => {},
+        }
+        out
+    }
+
+    // Executes one instruction unless a breakpoint stops execution before it.
+    pub fn cycle(&mut self, chip8: &mut Chip8) -> Option<StopReason> {
+        let reason = self.try_cycle(chip8).err();
+        if reason.is_some() {
+            self.pause();
+        }
+        reason
+    }
+
+    fn try_cycle(&mut self, chip8: &mut Chip8) -> Result<(), StopReason> {
+        if self.resumed_at.take() != Some(chip8.pc) {
+            if let Some(reason) = self.breakpoint(chip8) {
+                return Err(reason);
+            }
+        }
+
+        self.take_snapshot(chip8);
+        self.record_input(chip8);
+        if let Some(err) = self.tracer.as_mut().and_then(|tracer| tracer.record(chip8).err()) {
+            self.tracer = None;
+            return Err(StopReason::TraceFailed(err.to_string()));
+        }
+        self.execute_cycle(chip8)
+    }
+
+    // the breakpoint that stops execution before the instruction at pc
+    fn breakpoint(&self, chip8: &Chip8) -> Option<StopReason> {
+        let pc = chip8.pc;
+        if self.breakpoints.contains(&pc) {
+            return Some(StopReason::Breakpoint { pc });
+        }
+        let opcode = chip8.peek_word(pc)?;
+        let pattern = self.opcode_breakpoints.iter().find(|pattern| pattern.matches(opcode))?;
+        Some(StopReason::OpcodeBreakpoint { pc, opcode, pattern: *pattern })
+    }
+
+    fn execute_cycle(&mut self, chip8: &mut Chip8) -> Result<(), StopReason> {
+        let pc = chip8.pc;
+        match chip8.single_cycle() {
+            Ok(StepOutcome::Halted) => return Err(StopReason::Halted { pc }),
+            // the debugger counts instructions, it does not wait for the vblank
+            Ok(StepOutcome::Executed | StepOutcome::Drew) => {},
+            Err(err) => return Err(StopReason::Crashed(err)),
+        }
+
+        self.cycle += 1;
+        if self.cycle.is_multiple_of(self.cycles_per_timer_tick) {
+            chip8.delay_timer = chip8.delay_timer.saturating_sub(1);
+            chip8.sound_timer = chip8.sound_timer.saturating_sub(1);
+        }
+
+        // watchpoints stop after the instruction, so the new values can be inspected
+        let hits = chip8.take_watch_hits();
+        if !hits.is_empty() {
+            return Err(StopReason::Watchpoint(hits));
+        }
+        Ok(())
+    }
+
+    fn take_snapshot(&mut self, chip8: &mut Chip8) {
+        if !self.cycle.is_multiple_of(SNAPSHOT_INTERVAL) || self.snapshots.back().is_some_and(|snapshot| snapshot.cycle >= self.cycle) {
+            return;
+        }
+        self.push_snapshot(chip8);
+    }
+
+    // going back past the current cycle has to start from the edited state, replaying would lose the edit
+    fn take_edit_snapshot(&mut self, chip8: &mut Chip8) {
+        if self.snapshots.back().is_some_and(|snapshot| snapshot.cycle == self.cycle) {
+            self.snapshots.pop_back();
+        }
+        self.push_snapshot(chip8);
+    }
+
+    fn push_snapshot(&mut self, chip8: &mut Chip8) {
+        let mut state = Vec::new();
+        if chip8.save_state(&mut state).is_err() {
+            return;
+        }
+        self.snapshots.push_back(Snapshot { cycle: self.cycle, state });
+
+        if self.snapshots.len() > MAX_SNAPSHOTS {
+            self.snapshots.pop_front();
+            // only the last key states before the oldest snapshot are still needed
+            let oldest = self.snapshots[0].cycle;
+            let needed = self.inputs.partition_point(|(cycle, _)| *cycle <= oldest).saturating_sub(1);
+            self.inputs.drain(..needed);
+        }
+    }
+
+    fn record_input(&mut self, chip8: &Chip8) {
+        // after going backwards, the recorded future is replaced by what happens now
+        while self.inputs.last().is_some_and(|(cycle, _)| *cycle >= self.cycle) {
+            self.inputs.pop();
+        }
+        if self.inputs.last().map(|(_, keys)| keys) != Some(&chip8.key_state) {
+            self.inputs.push((self.cycle, chip8.key_state));
+        }
+    }
+
+    // executes the next instruction again, with the key states it originally ran with
+    fn replay_cycle(&mut self, chip8: &mut Chip8) -> Result<(), StopReason> {
+        let index = self.inputs.partition_point(|(cycle, _)| *cycle <= self.cycle);
+        if index > 0 {
+            chip8.key_state = self.inputs[index - 1].1;
+        }
+        self.take_snapshot(chip8);
+        self.execute_cycle(chip8)
+    }
+
+    // Goes back to the target cycle, or to the oldest snapshot if the target is older than that.
+    // false if there are no snapshots.
+    fn restore(&mut self, chip8: &mut Chip8, target: u64) -> bool {
+        let index = match self.snapshots.iter().rposition(|snapshot| snapshot.cycle <= target) {
+            Some(index) => index,
+            None if !self.snapshots.is_empty() => 0,
+            None => return false,
+        };
+        if chip8.load_state(&mut self.snapshots[index].state.as_slice()).is_err() {
+            return false;
+        }
+        self.cycle = self.snapshots[index].cycle;
+        // the later snapshots are taken again on the way forward
+        self.snapshots.truncate(index + 1);
+
+        while self.cycle < target {
+            match self.replay_cycle(chip8) {
+                Ok(()) | Err(StopReason::Watchpoint(_)) => {},
+                Err(_) => break,
+            }
+        }
+        true
+    }
+
+    // Goes back to the last place before the current cycle where execution would have stopped,
+    // searching one snapshot interval at a time.
+    fn reverse_continue(&mut self, chip8: &mut Chip8) -> Option<StopReason> {
+        let current = self.cycle;
+        let mut end = current;
+        loop {
+            let index = self.snapshots.iter().rposition(|snapshot| snapshot.cycle < end)?;
+            let start = self.snapshots[index].cycle;
+            self.restore(chip8, start);
+
+            // the last stop between start and end
+            let mut found = None;
+            while self.cycle < end {
+                if let Some(reason) = self.breakpoint(chip8) {
+                    found = Some((self.cycle, reason));
+                }
+                match self.replay_cycle(chip8) {
+                    Ok(()) => {},
+                    // the hit is shown after the instruction, which may be the current cycle
+                    Err(StopReason::Watchpoint(hits)) if self.cycle < current => found = Some((self.cycle, StopReason::Watchpoint(hits))),
+                    Err(StopReason::Watchpoint(_)) => {},
+                    Err(_) => break,
+                }
+            }
+
+            if let Some((cycle, reason)) = found {
+                self.restore(chip8, cycle);
+                // continuing from here should not stop on the same breakpoint again
+                self.resumed_at = Some(chip8.pc);
+                return Some(reason);
+            }
+            if index == 0 {
+                self.restore(chip8, start);
+                return None;
+            }
+            end = start;
+        }
+    }
+}
+
+fn set_register(chip8: &mut Chip8, register: Register, value: u16) -> Result<(), String> {
+    let byte = || u8::try_from(value).map_err(|_| format!("{:#X} does not fit in a byte", value));
+    match register {
+        Register::V(x) => chip8.v_registers[x as usize] = byte()?,
+        Register::I => chip8.i = value,
+        Register::Pc => chip8.pc = value,
+        Register::DelayTimer => chip8.delay_timer = byte()?,
+        Register::SoundTimer => chip8.sound_timer = byte()?,
+    }
+    Ok(())
+}
+
+// The instruction at pc, as it is shown after every step.
+pub fn location(chip8: &Chip8) -> String {
+    let Some(opcode) = chip8.peek_word(chip8.pc) else {
+        return format!("{:04X}  outside of memory", chip8.pc);
+    };
+    match decode(opcode) {
+        Some(instruction) => {
+            let long = chip8.peek_word(chip8.pc.wrapping_add(2));
+            format!("{:04X}  {:04X}  {}", chip8.pc, opcode, mnemonic(&instruction, Syntax::Octo, long, &BTreeSet::new()))
+        },
+        None => format!("{:04X}  {:04X}  unknown opcode", chip8.pc, opcode),
+    }
+}
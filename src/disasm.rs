@@ -0,0 +1,320 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::str::FromStr;
+
+use crate::instruction::{decode, Instruction};
+
+// Mnemonic syntax the disassembly is written in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Syntax {
+    #[default]
+    Octo,
+    // the syntax used in Cowgod's Chip-8 technical reference
+    Cowgod,
+}
+
+impl Syntax {
+    pub const NAMES: [&'static str; 2] = ["octo", "cowgod"];
+}
+
+impl FromStr for Syntax {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "octo" => Ok(Self::Octo),
+            "cowgod" => Ok(Self::Cowgod),
+            _ => Err(format!("Unknown syntax: {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LineKind {
+    Code(Instruction),
+    Data,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Line {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    pub kind: LineKind,
+}
+
+// A rom split into code and data, found by tracing every reachable instruction from the entry point.
+pub struct Disassembly {
+    pub lines: Vec<Line>,
+    // jump and call targets that start a line
+    pub labels: BTreeSet<u16>,
+    // jump and call targets in the middle of an instruction, which can not be labelled
+    pub inner_targets: BTreeSet<u16>,
+}
+
+// how many data bytes are shown on one line
+const DATA_BYTES_PER_LINE: usize = 8;
+
+impl Disassembly {
+    // rom is loaded at origin, and tracing starts at entry
+    pub fn trace(rom: &[u8], origin: u16, entry: u16) -> Self {
+        let end = origin as usize + rom.len();
+        let read_word = |address: usize| -> Option<u16> {
+            if address < origin as usize || address + 1 >= end {
+                return None;
+            }
+            let offset = address - origin as usize;
+            Some((rom[offset] as u16) << 8 | rom[offset + 1] as u16)
+        };
+
+        let mut code: BTreeMap<u16, Instruction> = BTreeMap::new();
+        let mut labels = BTreeSet::new();
+        let mut pending = vec![entry];
+
+        while let Some(address) = pending.pop() {
+            if code.contains_key(&address) {
+                continue;
+            }
+            let Some(instruction) = read_word(address as usize).and_then(decode) else {
+                continue;
+            };
+            // the long address of F000 NNNN has to be inside the rom as well
+            if instruction.size() == 4 && read_word(address as usize + 2).is_none() {
+                continue;
+            }
+            code.insert(address, instruction);
+
+            let next = address.wrapping_add(instruction.size());
+            match instruction {
+                Instruction::Jump { nnn } => {
+                    labels.insert(nnn);
+                    pending.push(nnn);
+                },
+                Instruction::Call { nnn } => {
+                    labels.insert(nnn);
+                    pending.push(nnn);
+                    pending.push(next);
+                },
+                Instruction::SkipEqImm { .. } |
+                Instruction::SkipNeImm { .. } |
+                Instruction::SkipEqReg { .. } |
+                Instruction::SkipNeReg { .. } |
+                Instruction::SkipKeyPressed { .. } |
+                Instruction::SkipKeyNotPressed { .. } => {
+                    pending.push(next);
+                    // the skipped instruction may be F000 NNNN, which is 4 bytes long
+                    let skipped_size = read_word(next as usize).and_then(decode).map_or(2, |skipped| skipped.size());
+                    pending.push(next.wrapping_add(skipped_size));
+                },
+                // the destination of these can not be known without running the rom
+                Instruction::Return | Instruction::Exit | Instruction::JumpOffset { .. } => {},
+                _ => pending.push(next),
+            }
+        }
+
+        // everything that was not reached is shown as data
+        let mut lines = Vec::new();
+        let mut address = origin as usize;
+        while address < end {
+            if let Some(instruction) = code.get(&(address as u16)) {
+                let size = instruction.size() as usize;
+                lines.push(Line {
+                    address: address as u16,
+                    bytes: rom[address - origin as usize..address - origin as usize + size].to_vec(),
+                    kind: LineKind::Code(*instruction),
+                });
+                address += size;
+                continue;
+            }
+
+            let start = address;
+            while address < end &&
+                address - start < DATA_BYTES_PER_LINE &&
+                !code.contains_key(&(address as u16)) &&
+                (address == start || !labels.contains(&(address as u16))) {
+                address += 1;
+            }
+            lines.push(Line {
+                address: start as u16,
+                bytes: rom[start - origin as usize..address - origin as usize].to_vec(),
+                kind: LineKind::Data,
+            });
+        }
+
+        // a target inside an instruction that was decoded from an earlier address has no line of its own
+        let starts: BTreeSet<u16> = lines.iter().map(|line| line.address).collect();
+        let (labels, inner_targets) = labels.into_iter()
+            .filter(|&target| (origin as usize..end).contains(&(target as usize)))
+            .partition(|target| starts.contains(target));
+
+        Self { lines, labels, inner_targets }
+    }
+
+    pub fn render(&self, syntax: Syntax) -> String {
+        let mut out = String::new();
+        for line in &self.lines {
+            if self.labels.contains(&line.address) {
+                match syntax {
+                    Syntax::Octo => writeln!(out, ": {}", label(line.address)).unwrap(),
+                    Syntax::Cowgod => writeln!(out, "{}:", label(line.address)).unwrap(),
+                }
+            }
+
+            let bytes = line.bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<_>>().join(" ");
+            let text = match &line.kind {
+                LineKind::Code(instruction) => {
+                    // F000 NNNN carries its address in the second word
+                    let long = (line.bytes.len() == 4).then(|| (line.bytes[2] as u16) << 8 | line.bytes[3] as u16);
+                    mnemonic(instruction, syntax, long, &self.labels)
+                },
+                LineKind::Data => data(&line.bytes, syntax),
+            };
+            writeln!(out, "{:04X}  {:<24}{}", line.address, bytes, text).unwrap();
+
+            let inside = line.address + 1..line.address + line.bytes.len() as u16;
+            for &target in self.inner_targets.range(inside) {
+                match syntax {
+                    Syntax::Octo => writeln!(out, "# 0x{:03X} is jumped to inside the line above", target).unwrap(),
+                    Syntax::Cowgod => writeln!(out, "; #{:03X} is jumped to inside the line above", target).unwrap(),
+                }
+            }
+        }
+        out
+    }
+}
+
+pub fn label(address: u16) -> String {
+    format!("L{:03X}", address)
+}
+
+fn data(bytes: &[u8], syntax: Syntax) -> String {
+    match syntax {
+        Syntax::Octo => bytes.iter().map(|byte| format!("0x{:02X}", byte)).collect::<Vec<_>>().join(" "),
+        Syntax::Cowgod => format!("DB {}", bytes.iter().map(|byte| format!("#{:02X}", byte)).collect::<Vec<_>>().join(", ")),
+    }
+}
+
+// Formats a single instruction, jump and call targets use their label if one exists.
+pub fn mnemonic(instruction: &Instruction, syntax: Syntax, long_address: Option<u16>, labels: &BTreeSet<u16>) -> String {
+    let target = |nnn: u16| -> String {
+        if labels.contains(&nnn) {
+            label(nnn)
+        }
+        else {
+            match syntax {
+                Syntax::Octo => format!("0x{:03X}", nnn),
+                Syntax::Cowgod => format!("#{:03X}", nnn),
+            }
+        }
+    };
+    let long = long_address.unwrap_or(0);
+
+    match syntax {
+        Syntax::Octo => match *instruction {
+            Instruction::Sys { nnn } => format!("0x{:02X} 0x{:02X}", nnn >> 8, nnn & 0xFF),
+            Instruction::ClearScreen => "clear".to_string(),
+            Instruction::Return => "return".to_string(),
+            Instruction::ScrollDown { n } => format!("scroll-down {}", n),
+            Instruction::ScrollUp { n } => format!("scroll-up {}", n),
+            Instruction::ScrollRight => "scroll-right".to_string(),
+            Instruction::ScrollLeft => "scroll-left".to_string(),
+            Instruction::Exit => "exit".to_string(),
+            Instruction::LowRes => "lores".to_string(),
+            Instruction::HighRes => "hires".to_string(),
+            Instruction::Jump { nnn } => format!("jump {}", target(nnn)),
+            Instruction::Call { nnn } => format!(":call {}", target(nnn)),
+            // octo conditions describe when the next instruction runs, the opposite of when it is skipped
+            Instruction::SkipEqImm { x, nn } => format!("if v{:X} != 0x{:02X} then", x, nn),
+            Instruction::SkipNeImm { x, nn } => format!("if v{:X} == 0x{:02X} then", x, nn),
+            Instruction::SkipEqReg { x, y } => format!("if v{:X} != v{:X} then", x, y),
+            Instruction::SaveRange { x, y } => format!("save v{:X} - v{:X}", x, y),
+            Instruction::LoadRange { x, y } => format!("load v{:X} - v{:X}", x, y),
+            Instruction::LoadImm { x, nn } => format!("v{:X} := 0x{:02X}", x, nn),
+            Instruction::AddImm { x, nn } => format!("v{:X} += 0x{:02X}", x, nn),
+            Instruction::Move { x, y } => format!("v{:X} := v{:X}", x, y),
+            Instruction::Or { x, y } => format!("v{:X} |= v{:X}", x, y),
+            Instruction::And { x, y } => format!("v{:X} &= v{:X}", x, y),
+            Instruction::Xor { x, y } => format!("v{:X} ^= v{:X}", x, y),
+            Instruction::Add { x, y } => format!("v{:X} += v{:X}", x, y),
+            Instruction::Sub { x, y } => format!("v{:X} -= v{:X}", x, y),
+            Instruction::ShiftRight { x, y } => format!("v{:X} >>= v{:X}", x, y),
+            Instruction::SubReverse { x, y } => format!("v{:X} =- v{:X}", x, y),
+            Instruction::ShiftLeft { x, y } => format!("v{:X} <<= v{:X}", x, y),
+            Instruction::SkipNeReg { x, y } => format!("if v{:X} == v{:X} then", x, y),
+            Instruction::LoadIndex { nnn } => format!("i := {}", target(nnn)),
+            Instruction::JumpOffset { nnn, .. } => format!("jump0 {}", target(nnn)),
+            Instruction::Random { x, nn } => format!("v{:X} := random 0x{:02X}", x, nn),
+            Instruction::Draw { x, y, n } => format!("sprite v{:X} v{:X} {}", x, y, n),
+            Instruction::SkipKeyPressed { x } => format!("if v{:X} -key then", x),
+            Instruction::SkipKeyNotPressed { x } => format!("if v{:X} key then", x),
+            Instruction::LoadLongIndex => format!("i := long 0x{:04X}", long),
+            Instruction::SelectPlanes { n } => format!("plane {}", n),
+            Instruction::LoadAudioPattern => "audio".to_string(),
+            Instruction::LoadDelay { x } => format!("v{:X} := delay", x),
+            Instruction::WaitKey { x } => format!("v{:X} := key", x),
+            Instruction::SetDelay { x } => format!("delay := v{:X}", x),
+            Instruction::SetSound { x } => format!("buzzer := v{:X}", x),
+            Instruction::AddIndex { x } => format!("i += v{:X}", x),
+            Instruction::LoadFont { x } => format!("i := hex v{:X}", x),
+            Instruction::LoadBigFont { x } => format!("i := bighex v{:X}", x),
+            Instruction::StoreBcd { x } => format!("bcd v{:X}", x),
+            Instruction::SetPitch { x } => format!("pitch := v{:X}", x),
+            Instruction::StoreRegisters { x } => format!("save v{:X}", x),
+            Instruction::LoadRegisters { x } => format!("load v{:X}", x),
+            Instruction::StoreFlags { x } => format!("saveflags v{:X}", x),
+            Instruction::LoadFlags { x } => format!("loadflags v{:X}", x),
+        },
+        Syntax::Cowgod => match *instruction {
+            Instruction::Sys { nnn } => format!("SYS {}", target(nnn)),
+            Instruction::ClearScreen => "CLS".to_string(),
+            Instruction::Return => "RET".to_string(),
+            Instruction::ScrollDown { n } => format!("SCD {}", n),
+            Instruction::ScrollUp { n } => format!("SCU {}", n),
+            Instruction::ScrollRight => "SCR".to_string(),
+            Instruction::ScrollLeft => "SCL".to_string(),
+            Instruction::Exit => "EXIT".to_string(),
+            Instruction::LowRes => "LOW".to_string(),
+            Instruction::HighRes => "HIGH".to_string(),
+            Instruction::Jump { nnn } => format!("JP {}", target(nnn)),
+            Instruction::Call { nnn } => format!("CALL {}", target(nnn)),
+            Instruction::SkipEqImm { x, nn } => format!("SE V{:X}, #{:02X}", x, nn),
+            Instruction::SkipNeImm { x, nn } => format!("SNE V{:X}, #{:02X}", x, nn),
+            Instruction::SkipEqReg { x, y } => format!("SE V{:X}, V{:X}", x, y),
+            Instruction::SaveRange { x, y } => format!("SAVE V{:X}, V{:X}", x, y),
+            Instruction::LoadRange { x, y } => format!("LOAD V{:X}, V{:X}", x, y),
+            Instruction::LoadImm { x, nn } => format!("LD V{:X}, #{:02X}", x, nn),
+            Instruction::AddImm { x, nn } => format!("ADD V{:X}, #{:02X}", x, nn),
+            Instruction::Move { x, y } => format!("LD V{:X}, V{:X}", x, y),
+            Instruction::Or { x, y } => format!("OR V{:X}, V{:X}", x, y),
+            Instruction::And { x, y } => format!("AND V{:X}, V{:X}", x, y),
+            Instruction::Xor { x, y } => format!("XOR V{:X}, V{:X}", x, y),
+            Instruction::Add { x, y } => format!("ADD V{:X}, V{:X}", x, y),
+            Instruction::Sub { x, y } => format!("SUB V{:X}, V{:X}", x, y),
+            Instruction::ShiftRight { x, y } => format!("SHR V{:X}, V{:X}", x, y),
+            Instruction::SubReverse { x, y } => format!("SUBN V{:X}, V{:X}", x, y),
+            Instruction::ShiftLeft { x, y } => format!("SHL V{:X}, V{:X}", x, y),
+            Instruction::SkipNeReg { x, y } => format!("SNE V{:X}, V{:X}", x, y),
+            Instruction::LoadIndex { nnn } => format!("LD I, {}", target(nnn)),
+            Instruction::JumpOffset { nnn, .. } => format!("JP V0, {}", target(nnn)),
+            Instruction::Random { x, nn } => format!("RND V{:X}, #{:02X}", x, nn),
+            Instruction::Draw { x, y, n } => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::SkipKeyPressed { x } => format!("SKP V{:X}", x),
+            Instruction::SkipKeyNotPressed { x } => format!("SKNP V{:X}", x),
+            Instruction::LoadLongIndex => format!("LD I, LONG #{:04X}", long),
+            Instruction::SelectPlanes { n } => format!("PLANE {}", n),
+            Instruction::LoadAudioPattern => "AUDIO".to_string(),
+            Instruction::LoadDelay { x } => format!("LD V{:X}, DT", x),
+            Instruction::WaitKey { x } => format!("LD V{:X}, K", x),
+            Instruction::SetDelay { x } => format!("LD DT, V{:X}", x),
+            Instruction::SetSound { x } => format!("LD ST, V{:X}", x),
+            Instruction::AddIndex { x } => format!("ADD I, V{:X}", x),
+            Instruction::LoadFont { x } => format!("LD F, V{:X}", x),
+            Instruction::LoadBigFont { x } => format!("LD HF, V{:X}", x),
+            Instruction::StoreBcd { x } => format!("LD B, V{:X}", x),
+            Instruction::SetPitch { x } => format!("PITCH V{:X}", x),
+            Instruction::StoreRegisters { x } => format!("LD [I], V{:X}", x),
+            Instruction::LoadRegisters { x } => format!("LD V{:X}, [I]", x),
+            Instruction::StoreFlags { x } => format!("LD R, V{:X}", x),
+            Instruction::LoadFlags { x } => format!("LD V{:X}, R", x),
+        },
+    }
+}
@@ -1,4 +1,5 @@
-use cpal::BuildStreamError;
+use cpal::{BuildStreamError, PlayStreamError};
+use savefile::SavefileError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -7,4 +8,42 @@ pub enum BeeperError {
     NoDefaultOutputDevice,
     #[error("Error occured whilst building stream: {0}")]
     BuildStream(#[from] BuildStreamError),
-}
\ No newline at end of file
+    #[error("Error occured whilst playing stream: {0}")]
+    PlayStream(#[from] PlayStreamError),
+}
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8Error {
+    #[error("Stack overflow at {pc:#05X} executing {opcode:#06X}")]
+    StackOverflow { pc: u16, opcode: u16 },
+    #[error("Stack underflow at {pc:#05X} executing {opcode:#06X}")]
+    StackUnderflow { pc: u16, opcode: u16 },
+    #[error("Unknown opcode {opcode:#06X} at {pc:#05X}")]
+    UnknownOpcode { pc: u16, opcode: u16 },
+    #[error("Memory address {address:#05X} out of range at {pc:#05X} executing {opcode:#06X}")]
+    MemoryOutOfRange { pc: u16, opcode: u16, address: usize },
+}
+
+#[derive(Error, Debug)]
+pub enum AssembleError {
+    #[error("{file}:{line}: {message}")]
+    Syntax { file: String, line: usize, message: String },
+    #[error("Could not read {path}: {source}")]
+    Io { path: String, source: std::io::Error },
+}
+
+#[derive(Error, Debug)]
+pub enum SaveStateError {
+    #[error("Could not read or write the state: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("The state is corrupt: {0}")]
+    Savefile(#[from] SavefileError),
+    #[error("Not a save state: {0}")]
+    NotAState(SavefileError),
+    #[error("The state is cut off")]
+    Truncated,
+    #[error("The state has format version {version}, which is newer than this emulator supports")]
+    UnsupportedVersion { version: u32 },
+    #[error("The state belongs to a different rom (crc32 {state:08X}, the loaded rom is {rom:08X})")]
+    WrongRom { state: u32, rom: u32 },
+}
@@ -0,0 +1,272 @@
+// A single decoded instruction, x and y are register indices, n is a nibble,
+// nn is a byte and nnn is a 12 bit address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    // 0NNN - call machine code routine at NNN, ignored by modern interpreters
+    Sys { nnn: u16 },
+    // 00E0 - clear the display
+    ClearScreen,
+    // 00EE - return from subroutine
+    Return,
+    // 00CN - scroll display N pixels down
+    ScrollDown { n: u8 },
+    // 00DN - scroll display N pixels up (XO-CHIP)
+    ScrollUp { n: u8 },
+    // 00FB - scroll display 4 pixels right
+    ScrollRight,
+    // 00FC - scroll display 4 pixels left
+    ScrollLeft,
+    // 00FD - exit the interpreter
+    Exit,
+    // 00FE - switch to lores (64x32) mode
+    LowRes,
+    // 00FF - switch to hires (128x64) mode
+    HighRes,
+    // 1NNN - jump to address NNN
+    Jump { nnn: u16 },
+    // 2NNN - call subroutine at NNN
+    Call { nnn: u16 },
+    // 3XNN - skip next instruction if VX == NN
+    SkipEqImm { x: u8, nn: u8 },
+    // 4XNN - skip next instruction if VX != NN
+    SkipNeImm { x: u8, nn: u8 },
+    // 5XY0 - skip next instruction if VX == VY
+    SkipEqReg { x: u8, y: u8 },
+    // 5XY2 - store VX to VY in memory starting at address I (XO-CHIP)
+    SaveRange { x: u8, y: u8 },
+    // 5XY3 - read VX to VY from memory starting at address I (XO-CHIP)
+    LoadRange { x: u8, y: u8 },
+    // 6XNN - set VX to NN
+    LoadImm { x: u8, nn: u8 },
+    // 7XNN - add NN to VX
+    AddImm { x: u8, nn: u8 },
+    // 8XY0 - set VX to VY
+    Move { x: u8, y: u8 },
+    // 8XY1 - set VX to VX | VY
+    Or { x: u8, y: u8 },
+    // 8XY2 - set VX to VX & VY
+    And { x: u8, y: u8 },
+    // 8XY3 - set VX to VX ^ VY
+    Xor { x: u8, y: u8 },
+    // 8XY4 - set VX to VX + VY, set VF to 1 if carry
+    Add { x: u8, y: u8 },
+    // 8XY5 - set VX to VX - VY, set VF to 0 if borrow
+    Sub { x: u8, y: u8 },
+    // 8XY6 - set VX to VY >> 1, set VF to LSB
+    ShiftRight { x: u8, y: u8 },
+    // 8XY7 - set VX to VY - VX, set VF to 0 if borrow
+    SubReverse { x: u8, y: u8 },
+    // 8XYE - set VX to VY << 1, set VF to MSB
+    ShiftLeft { x: u8, y: u8 },
+    // 9XY0 - skip next instruction if VX != VY
+    SkipNeReg { x: u8, y: u8 },
+    // ANNN - set I to NNN
+    LoadIndex { nnn: u16 },
+    // BNNN - jump to address NNN + V0, x is used with the jumping quirk
+    JumpOffset { x: u8, nnn: u16 },
+    // CXNN - set VX to random byte ANDed with NN
+    Random { x: u8, nn: u8 },
+    // DXYN - draw sprite at VX, VY with N bytes of sprite data starting at I
+    Draw { x: u8, y: u8, n: u8 },
+    // EX9E - skip next instruction if key in VX is pressed
+    SkipKeyPressed { x: u8 },
+    // EXA1 - skip next instruction if key in VX is not pressed
+    SkipKeyNotPressed { x: u8 },
+    // F000 NNNN - set I to the 16 bit address stored in the next word (XO-CHIP)
+    LoadLongIndex,
+    // FN01 - select the bitplanes N (XO-CHIP)
+    SelectPlanes { n: u8 },
+    // F002 - load the 16 byte audio pattern starting at address I (XO-CHIP)
+    LoadAudioPattern,
+    // FX07 - set VX to delay timer value
+    LoadDelay { x: u8 },
+    // FX0A - wait for keypress, store in VX
+    WaitKey { x: u8 },
+    // FX15 - set delay timer to VX
+    SetDelay { x: u8 },
+    // FX18 - set sound timer to VX
+    SetSound { x: u8 },
+    // FX1E - add VX to I
+    AddIndex { x: u8 },
+    // FX29 - set I to location of sprite for digit VX
+    LoadFont { x: u8 },
+    // FX30 - set I to location of the big sprite for digit VX
+    LoadBigFont { x: u8 },
+    // FX33 - store BCD representation of VX in memory locations I, I+1, and I+2
+    StoreBcd { x: u8 },
+    // FX3A - set the audio pattern pitch register to VX (XO-CHIP)
+    SetPitch { x: u8 },
+    // FX55 - store V0 to VX in memory starting at address I
+    StoreRegisters { x: u8 },
+    // FX65 - read V0 to VX from memory starting at address I
+    LoadRegisters { x: u8 },
+    // FX75 - store V0 to VX in the RPL user flags
+    StoreFlags { x: u8 },
+    // FX85 - read V0 to VX from the RPL user flags
+    LoadFlags { x: u8 },
+}
+
+impl Instruction {
+    // instructions that are only available in XO-CHIP mode
+    pub fn is_xo_chip(&self) -> bool {
+        matches!(
+            self,
+            Instruction::ScrollUp { .. } |
+            Instruction::SaveRange { .. } |
+            Instruction::LoadRange { .. } |
+            Instruction::LoadLongIndex |
+            Instruction::SelectPlanes { .. } |
+            Instruction::LoadAudioPattern |
+            Instruction::SetPitch { .. }
+        )
+    }
+
+    // length of the instruction in bytes, F000 NNNN is the only 4 byte long instruction
+    pub fn size(&self) -> u16 {
+        match self {
+            Instruction::LoadLongIndex => 4,
+            _ => 2,
+        }
+    }
+
+    // The opcode of the instruction, the inverse of decode. F000 NNNN only encodes its first word.
+    pub fn encode(&self) -> u16 {
+        let xy = |x: u8, y: u8| (x as u16) << 8 | (y as u16) << 4;
+        let xnn = |x: u8, nn: u8| (x as u16) << 8 | nn as u16;
+        match *self {
+            Instruction::Sys { nnn } => nnn & 0x0FFF,
+            Instruction::ClearScreen => 0x00E0,
+            Instruction::Return => 0x00EE,
+            Instruction::ScrollDown { n } => 0x00C0 | (n & 0xF) as u16,
+            Instruction::ScrollUp { n } => 0x00D0 | (n & 0xF) as u16,
+            Instruction::ScrollRight => 0x00FB,
+            Instruction::ScrollLeft => 0x00FC,
+            Instruction::Exit => 0x00FD,
+            Instruction::LowRes => 0x00FE,
+            Instruction::HighRes => 0x00FF,
+            Instruction::Jump { nnn } => 0x1000 | (nnn & 0x0FFF),
+            Instruction::Call { nnn } => 0x2000 | (nnn & 0x0FFF),
+            Instruction::SkipEqImm { x, nn } => 0x3000 | xnn(x, nn),
+            Instruction::SkipNeImm { x, nn } => 0x4000 | xnn(x, nn),
+            Instruction::SkipEqReg { x, y } => 0x5000 | xy(x, y),
+            Instruction::SaveRange { x, y } => 0x5002 | xy(x, y),
+            Instruction::LoadRange { x, y } => 0x5003 | xy(x, y),
+            Instruction::LoadImm { x, nn } => 0x6000 | xnn(x, nn),
+            Instruction::AddImm { x, nn } => 0x7000 | xnn(x, nn),
+            Instruction::Move { x, y } => 0x8000 | xy(x, y),
+            Instruction::Or { x, y } => 0x8001 | xy(x, y),
+            Instruction::And { x, y } => 0x8002 | xy(x, y),
+            Instruction::Xor { x, y } => 0x8003 | xy(x, y),
+            Instruction::Add { x, y } => 0x8004 | xy(x, y),
+            Instruction::Sub { x, y } => 0x8005 | xy(x, y),
+            Instruction::ShiftRight { x, y } => 0x8006 | xy(x, y),
+            Instruction::SubReverse { x, y } => 0x8007 | xy(x, y),
+            Instruction::ShiftLeft { x, y } => 0x800E | xy(x, y),
+            Instruction::SkipNeReg { x, y } => 0x9000 | xy(x, y),
+            Instruction::LoadIndex { nnn } => 0xA000 | (nnn & 0x0FFF),
+            Instruction::JumpOffset { nnn, .. } => 0xB000 | (nnn & 0x0FFF),
+            Instruction::Random { x, nn } => 0xC000 | xnn(x, nn),
+            Instruction::Draw { x, y, n } => 0xD000 | xy(x, y) | (n & 0xF) as u16,
+            Instruction::SkipKeyPressed { x } => 0xE09E | xy(x, 0),
+            Instruction::SkipKeyNotPressed { x } => 0xE0A1 | xy(x, 0),
+            Instruction::LoadLongIndex => 0xF000,
+            Instruction::SelectPlanes { n } => 0xF001 | xy(n, 0),
+            Instruction::LoadAudioPattern => 0xF002,
+            Instruction::LoadDelay { x } => 0xF007 | xy(x, 0),
+            Instruction::WaitKey { x } => 0xF00A | xy(x, 0),
+            Instruction::SetDelay { x } => 0xF015 | xy(x, 0),
+            Instruction::SetSound { x } => 0xF018 | xy(x, 0),
+            Instruction::AddIndex { x } => 0xF01E | xy(x, 0),
+            Instruction::LoadFont { x } => 0xF029 | xy(x, 0),
+            Instruction::LoadBigFont { x } => 0xF030 | xy(x, 0),
+            Instruction::StoreBcd { x } => 0xF033 | xy(x, 0),
+            Instruction::SetPitch { x } => 0xF03A | xy(x, 0),
+            Instruction::StoreRegisters { x } => 0xF055 | xy(x, 0),
+            Instruction::LoadRegisters { x } => 0xF065 | xy(x, 0),
+            Instruction::StoreFlags { x } => 0xF075 | xy(x, 0),
+            Instruction::LoadFlags { x } => 0xF085 | xy(x, 0),
+        }
+    }
+}
+
+// Decodes an opcode, None if the opcode is not a known instruction.
+pub fn decode(opcode: u16) -> Option<Instruction> {
+    let x = ((opcode & 0x0F00) >> 8) as u8;
+    let y = ((opcode & 0x00F0) >> 4) as u8;
+    let n = (opcode & 0x000F) as u8;
+    let nn = (opcode & 0x00FF) as u8;
+    let nnn = opcode & 0x0FFF;
+
+    let instruction = match opcode & 0xF000 {
+        0x0000 => match opcode {
+            0x00E0 => Instruction::ClearScreen,
+            0x00EE => Instruction::Return,
+            0x00FB => Instruction::ScrollRight,
+            0x00FC => Instruction::ScrollLeft,
+            0x00FD => Instruction::Exit,
+            0x00FE => Instruction::LowRes,
+            0x00FF => Instruction::HighRes,
+            _ if opcode & 0xFFF0 == 0x00C0 => Instruction::ScrollDown { n },
+            _ if opcode & 0xFFF0 == 0x00D0 => Instruction::ScrollUp { n },
+            _ => Instruction::Sys { nnn },
+        },
+        0x1000 => Instruction::Jump { nnn },
+        0x2000 => Instruction::Call { nnn },
+        0x3000 => Instruction::SkipEqImm { x, nn },
+        0x4000 => Instruction::SkipNeImm { x, nn },
+        0x5000 => match n {
+            0x0 => Instruction::SkipEqReg { x, y },
+            0x2 => Instruction::SaveRange { x, y },
+            0x3 => Instruction::LoadRange { x, y },
+            _ => return None,
+        },
+        0x6000 => Instruction::LoadImm { x, nn },
+        0x7000 => Instruction::AddImm { x, nn },
+        0x8000 => match n {
+            0x0 => Instruction::Move { x, y },
+            0x1 => Instruction::Or { x, y },
+            0x2 => Instruction::And { x, y },
+            0x3 => Instruction::Xor { x, y },
+            0x4 => Instruction::Add { x, y },
+            0x5 => Instruction::Sub { x, y },
+            0x6 => Instruction::ShiftRight { x, y },
+            0x7 => Instruction::SubReverse { x, y },
+            0xE => Instruction::ShiftLeft { x, y },
+            _ => return None,
+        },
+        0x9000 => match n {
+            0x0 => Instruction::SkipNeReg { x, y },
+            _ => return None,
+        },
+        0xA000 => Instruction::LoadIndex { nnn },
+        0xB000 => Instruction::JumpOffset { x, nnn },
+        0xC000 => Instruction::Random { x, nn },
+        0xD000 => Instruction::Draw { x, y, n },
+        0xE000 => match nn {
+            0x9E => Instruction::SkipKeyPressed { x },
+            0xA1 => Instruction::SkipKeyNotPressed { x },
+            _ => return None,
+        },
+        0xF000 => match nn {
+            0x00 if x == 0 => Instruction::LoadLongIndex,
+            0x01 => Instruction::SelectPlanes { n: x },
+            0x02 if x == 0 => Instruction::LoadAudioPattern,
+            0x07 => Instruction::LoadDelay { x },
+            0x0A => Instruction::WaitKey { x },
+            0x15 => Instruction::SetDelay { x },
+            0x18 => Instruction::SetSound { x },
+            0x1E => Instruction::AddIndex { x },
+            0x29 => Instruction::LoadFont { x },
+            0x30 => Instruction::LoadBigFont { x },
+            0x33 => Instruction::StoreBcd { x },
+            0x3A => Instruction::SetPitch { x },
+            0x55 => Instruction::StoreRegisters { x },
+            0x65 => Instruction::LoadRegisters { x },
+            0x75 => Instruction::StoreFlags { x },
+            0x85 => Instruction::LoadFlags { x },
+            _ => return None,
+        },
+        _ => unreachable!(),
+    };
+    Some(instruction)
+}
\ No newline at end of file
@@ -0,0 +1,62 @@
+// serde formats for the Chip8 fields that are unreadable as plain json arrays, see Chip8::to_json.
+
+// Memory as a hex dump, a string per 16 bytes: "0200: 00 E0 A2 2A 60 0C 61 08 D0 1F 70 09 A2 39 D0 1F".
+// The address of every line has to follow on from the line before it.
+pub mod hex_memory {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    const BYTES_PER_LINE: usize = 16;
+
+    pub fn serialize<S: Serializer>(memory: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(memory.chunks(BYTES_PER_LINE).enumerate().map(|(line, bytes)| {
+            let bytes: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+            format!("{:04X}: {}", line * BYTES_PER_LINE, bytes.join(" "))
+        }))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let mut memory = Vec::new();
+        for line in Vec::<String>::deserialize(deserializer)? {
+            let (address, bytes) = line.split_once(':').ok_or_else(|| D::Error::custom(format!("memory line without an address: {}", line)))?;
+            let address = usize::from_str_radix(address.trim(), 16).map_err(|_| D::Error::custom(format!("bad memory address: {}", line)))?;
+            if address != memory.len() {
+                return Err(D::Error::custom(format!("memory line {:04X} should be at {:04X}", address, memory.len())));
+            }
+            for byte in bytes.split_whitespace() {
+                let byte = u8::from_str_radix(byte, 16).map_err(|_| D::Error::custom(format!("bad byte {} at {:04X}", byte, address)))?;
+                memory.push(byte);
+            }
+        }
+        Ok(memory)
+    }
+}
+
+// The display as ascii art, a string per row with the characters of utils::render_ascii.
+pub mod ascii_display {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    use crate::{chip8::{HIRES_HEIGHT, HIRES_WIDTH, LORES_WIDTH}, utils::ASCII_PIXELS};
+
+    pub fn serialize<S: Serializer>(display: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        let width = if display.len() == HIRES_WIDTH * HIRES_HEIGHT { HIRES_WIDTH } else { LORES_WIDTH };
+        serializer.collect_seq(display.chunks(width).map(|row| {
+            row.iter().map(|&pixel| ASCII_PIXELS[(pixel & 0b11) as usize]).collect::<String>()
+        }))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let rows = Vec::<String>::deserialize(deserializer)?;
+        let mut display = Vec::new();
+        for (y, row) in rows.iter().enumerate() {
+            if row.chars().count() != rows[0].chars().count() {
+                return Err(D::Error::custom(format!("display row {} is not as long as the first row", y)));
+            }
+            for character in row.chars() {
+                let pixel = ASCII_PIXELS.iter().position(|&pixel| pixel == character)
+                    .ok_or_else(|| D::Error::custom(format!("display row {} has {:?}, pixels are one of {:?}", y, character, ASCII_PIXELS)))?;
+                display.push(pixel as u8);
+            }
+        }
+        Ok(display)
+    }
+}
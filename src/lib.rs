@@ -1,8 +1,24 @@
+// savefile-derive expands its impls inside an anonymous const
+#![allow(non_local_definitions)]
+
+pub mod assembler;
 pub mod chip8;
+pub mod debugger;
+pub mod disasm;
 pub mod errors;
 pub mod input;
+pub mod instruction;
+pub mod json;
+pub mod machine;
+pub mod movie;
 pub mod audio;
 pub mod options;
+pub mod quirks;
+pub mod rewind;
+pub mod rng;
+pub mod savestate;
+pub mod trace;
 pub mod utils;
+pub mod watchpoint;
 #[cfg(target_arch = "wasm32")]
 pub mod wasm;
\ No newline at end of file
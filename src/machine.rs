@@ -0,0 +1,355 @@
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::audio::AudioSink;
+use crate::chip8::{Chip8, StepOutcome};
+use crate::errors::Chip8Error;
+use crate::movie::Movie;
+use crate::trace::Tracer;
+
+// the rate of the delay and sound timers, and of the frames the machine runs in
+pub const FRAMES_PER_SECOND: u64 = 60;
+pub const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / FRAMES_PER_SECOND);
+// run_due does not catch up on more than this, so a stalled host does not run seconds of frames at once
+pub const MAX_CATCH_UP: Duration = Duration::from_millis(250);
+
+// Where a Machine gets the time from, wall clock time for frontends.
+pub trait Clock {
+    // time since a fixed point, never decreasing
+    fn now(&self) -> Duration;
+}
+
+// std::time::Instant is not available on the web, the wasm frontend has its own clock
+#[cfg(not(target_arch = "wasm32"))]
+pub struct SystemClock {
+    start: std::time::Instant,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl SystemClock {
+    pub fn new() -> Self {
+        Self { start: std::time::Instant::now() }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+// A clock that only moves when it is advanced, for tests and runs that do not follow the wall clock.
+// Clones share the time, so a test can keep one and hand the other to a Machine.
+#[derive(Clone, Debug, Default)]
+pub struct VirtualClock {
+    now: Arc<Mutex<Duration>>,
+}
+
+impl VirtualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Duration {
+        *self.now.lock().unwrap()
+    }
+}
+
+// Drives a Chip8 in 60 Hz frames. A frame runs hz / 60 instructions, the remainder is carried over
+// to later frames so every speed is exact, then ticks the timers and updates the audio sink.
+// With the display wait quirk a frame also ends after a sprite is drawn.
+// Frontends call run_due and sleep for until_next_frame, rendering is up to them.
+pub struct Machine {
+    pub chip8: Chip8,
+    clock: Box<dyn Clock + Send>,
+    audio: Box<dyn AudioSink + Send>,
+    tracer: Option<Tracer>,
+    // the error tracing stopped with, until it is taken
+    trace_error: Option<io::Error>,
+    // the keys of every frame are recorded into the movie, or played back from it
+    movie: Option<Movie>,
+    recording: bool,
+    hz: u64,
+    // instructions owed to the next frame, in 60ths
+    cycle_remainder: u64,
+    // emulated time that has not been run yet, less than a frame
+    pending: Duration,
+    // the clock when run_due was last called
+    last_run: Duration,
+    frames: u64,
+    cycles: u64,
+}
+
+impl Machine {
+    pub fn new(chip8: Chip8, hz: u64, clock: Box<dyn Clock + Send>, audio: Box<dyn AudioSink + Send>) -> Self {
+        let last_run = clock.now();
+        Self {
+            chip8,
+            clock,
+            audio,
+            tracer: None,
+            trace_error: None,
+            movie: None,
+            recording: false,
+            hz,
+            cycle_remainder: 0,
+            pending: Duration::ZERO,
+            last_run,
+            frames: 0,
+            cycles: 0,
+        }
+    }
+
+    pub fn hz(&self) -> u64 {
+        self.hz
+    }
+
+    pub fn set_hz(&mut self, hz: u64) {
+        self.hz = hz;
+    }
+
+    // instructions in a frame, rounded down but at least 1, for drivers that cannot spread the remainder
+    pub fn cycles_per_frame(&self) -> u64 {
+        (self.hz / FRAMES_PER_SECOND).max(1)
+    }
+
+    // records every instruction that is executed from now on
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = Some(tracer);
+        self.trace_error = None;
+    }
+
+    // Tracing stops when writing the trace fails, this is the error it failed with.
+    pub fn take_trace_error(&mut self) -> Option<io::Error> {
+        self.trace_error.take()
+    }
+
+    // Records the keys held in every frame from now on, the movie starts out empty at power on.
+    pub fn record_movie(&mut self, movie: Movie) {
+        self.movie = Some(movie);
+        self.recording = true;
+    }
+
+    // Plays back the keys of a movie, the machine has to be set up with Movie::machine.
+    pub fn play_movie(&mut self, movie: Movie) {
+        self.movie = Some(movie);
+        self.recording = false;
+    }
+
+    // stops recording or playing, returning the movie
+    pub fn take_movie(&mut self) -> Option<Movie> {
+        self.movie.take()
+    }
+
+    pub fn is_recording_movie(&self) -> bool {
+        self.recording && self.movie.is_some()
+    }
+
+    // true until the last frame of the movie has run, input from anywhere else would make it go out of sync
+    pub fn is_playing_movie(&self) -> bool {
+        !self.recording && self.movie.as_ref().is_some_and(|movie| self.frames < movie.frames)
+    }
+
+    // frames that ran to the end
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    // instructions executed
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    // Runs a frame, stopping early if the machine halts or crashes, in which case the timers do not tick.
+    pub fn run_frame(&mut self) -> Result<StepOutcome, Chip8Error> {
+        // keys only change between frames, so the keys at the start of each frame replay a run exactly
+        if let Some(movie) = &mut self.movie {
+            if self.recording {
+                movie.input.record(self.frames, self.chip8.key_state);
+            }
+            else if let Some(keys) = movie.input.keys_at(self.frames) {
+                self.chip8.key_state = keys;
+            }
+        }
+
+        self.cycle_remainder += self.hz;
+        let cycles = self.cycle_remainder / FRAMES_PER_SECOND;
+        self.cycle_remainder %= FRAMES_PER_SECOND;
+
+        for _ in 0..cycles {
+            if let Some(err) = self.tracer.as_mut().and_then(|tracer| tracer.record(&self.chip8).err()) {
+                self.tracer = None;
+                self.trace_error = Some(err);
+            }
+            match self.chip8.single_cycle() {
+                Ok(StepOutcome::Executed) => self.cycles += 1,
+                // the rest of the frame is spent waiting for the vblank
+                Ok(StepOutcome::Drew) => {
+                    self.cycles += 1;
+                    break;
+                },
+                Ok(StepOutcome::Halted) => {
+                    self.silence();
+                    return Ok(StepOutcome::Halted);
+                },
+                Err(err) => {
+                    self.silence();
+                    return Err(err);
+                },
+            }
+        }
+
+        self.update_audio();
+        self.chip8.delay_timer = self.chip8.delay_timer.saturating_sub(1);
+        self.chip8.sound_timer = self.chip8.sound_timer.saturating_sub(1);
+        if let Some(tracer) = &mut self.tracer {
+            // flushed once per frame, so little is lost when the process exits
+            tracer.flush().ok();
+        }
+        self.frames += 1;
+        if let Some(movie) = self.movie.as_mut().filter(|_| self.recording) {
+            movie.frames = self.frames;
+        }
+        Ok(StepOutcome::Executed)
+    }
+
+    // Runs the frames that fit into duration, plus what was left over from earlier calls.
+    pub fn run_for(&mut self, duration: Duration) -> Result<StepOutcome, Chip8Error> {
+        self.pending += duration;
+        while self.pending >= FRAME_DURATION {
+            self.pending -= FRAME_DURATION;
+            if self.run_frame()? == StepOutcome::Halted {
+                return Ok(StepOutcome::Halted);
+            }
+        }
+        Ok(StepOutcome::Executed)
+    }
+
+    // runs the frames that are due since the last call, by the clock
+    pub fn run_due(&mut self) -> Result<StepOutcome, Chip8Error> {
+        let now = self.clock.now();
+        let elapsed = now.saturating_sub(self.last_run).min(MAX_CATCH_UP);
+        self.last_run = now;
+        self.run_for(elapsed)
+    }
+
+    // how long the host can sleep before the next frame is due
+    pub fn until_next_frame(&self) -> Duration {
+        let due = self.pending + self.clock.now().saturating_sub(self.last_run);
+        FRAME_DURATION.saturating_sub(due)
+    }
+
+    // Forgets the time that passed since the last run, for resuming after the machine was paused.
+    pub fn sync(&mut self) {
+        self.last_run = self.clock.now();
+        self.pending = Duration::ZERO;
+    }
+
+    // plays the sound while the sound timer is active, for drivers that tick the timers themselves
+    pub fn update_audio(&mut self) {
+        let sound = self.chip8.is_sounding().then(|| self.chip8.sound());
+        self.audio.set_sound(sound);
+    }
+
+    pub fn silence(&mut self) {
+        self.audio.set_sound(None);
+    }
+
+    // true if the next instruction jumps to itself, which test roms end with
+    pub fn is_stuck(&self) -> bool {
+        self.chip8.peek_word(self.chip8.pc) == Some(0x1000 | self.chip8.pc)
+    }
+}
+
+// Keys held down over time, for running roms without a keyboard. Every line is a frame number
+// followed by the keys held from that frame on, as hex digits, or `-` for none:
+//
+//     # press 5 for 10 frames one second in
+//     60 5
+//     70 -
+//     120 4 6
+//
+// Everything after a `#` is a comment.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InputScript {
+    // sorted by frame
+    changes: Vec<(u64, [u8; 16])>,
+}
+
+impl InputScript {
+    // the keys to hold from this frame on, if they change at it
+    pub fn keys_at(&self, frame: u64) -> Option<[u8; 16]> {
+        self.changes
+            .binary_search_by_key(&frame, |(start, _)| *start)
+            .ok()
+            .map(|index| self.changes[index].1)
+    }
+
+    // Holds the keys from this frame on, if they are not the keys held already. Frames are recorded in order.
+    pub fn record(&mut self, frame: u64, keys: [u8; 16]) {
+        let keys = keys.map(|key| (key != 0) as u8);
+        if self.changes.last().is_none_or(|(_, held)| *held != keys) {
+            self.changes.push((frame, keys));
+        }
+    }
+}
+
+// in the format FromStr reads
+impl fmt::Display for InputScript {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (frame, keys) in &self.changes {
+            let held: Vec<String> = (0..16).filter(|&key| keys[key] != 0).map(|key| format!("{:X}", key)).collect();
+            writeln!(f, "{} {}", frame, if held.is_empty() { "-".to_string() } else { held.join(" ") })?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for InputScript {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut changes: Vec<(u64, [u8; 16])> = Vec::new();
+        for (index, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap();
+            let mut words = line.split_whitespace();
+            let Some(frame) = words.next() else {
+                continue;
+            };
+            let frame = frame.parse::<u64>().map_err(|_| format!("line {}: invalid frame {}", index + 1, frame))?;
+            if changes.last().is_some_and(|(last, _)| *last >= frame) {
+                return Err(format!("line {}: frame {} is not after the previous line", index + 1, frame));
+            }
+
+            let mut keys = [0; 16];
+            for word in words {
+                if word == "-" {
+                    continue;
+                }
+                for c in word.chars() {
+                    let key = c.to_digit(16).ok_or_else(|| format!("line {}: invalid key {}", index + 1, c))?;
+                    keys[key as usize] = 1;
+                }
+            }
+            changes.push((frame, keys));
+        }
+        Ok(Self { changes })
+    }
+}
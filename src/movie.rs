@@ -0,0 +1,120 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{chip8::{Chip8, MachineMode}, machine::InputScript, quirks::Quirks, savestate::rom_hash};
+
+// Input movies, the keys held in every frame of a run from power on, with everything else the run
+// depends on so it plays back bit for bit: the rom, the seed of CXNN, the machine and its speed.
+//
+// A movie is a text file, a header followed by an empty line and the keys as an InputScript:
+//
+//     chip-8-emu movie 1
+//     rom 1A2B3C4D
+//     seed 42
+//     mode chip8
+//     hz 600
+//     frames 3600
+//     quirks {"vf_reset":false,"shifting":true,...}
+//
+//     0 -
+//     60 5
+//     70 -
+pub const MOVIE_VERSION: u32 = 1;
+const MAGIC: &str = "chip-8-emu movie";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Movie {
+    // crc32 of the rom, see savestate::rom_hash
+    pub rom_hash: u32,
+    pub seed: u64,
+    pub mode: MachineMode,
+    pub quirks: Quirks,
+    // instructions per second, see Machine::hz
+    pub hz: u64,
+    // the length of the movie, playback ends after this many frames
+    pub frames: u64,
+    pub input: InputScript,
+}
+
+impl Movie {
+    // an empty movie to record a run of this rom into
+    pub fn new(rom: &[u8], seed: u64, mode: MachineMode, quirks: Quirks, hz: u64) -> Self {
+        Self {
+            rom_hash: rom_hash(rom),
+            seed,
+            mode,
+            quirks,
+            hz,
+            frames: 0,
+            input: InputScript::default(),
+        }
+    }
+
+    // The machine at power on, as the movie was recorded from. Fails if the movie was recorded with another rom.
+    pub fn machine(&self, rom: &[u8]) -> Result<Chip8, String> {
+        if rom_hash(rom) != self.rom_hash {
+            return Err(format!("The movie was recorded with a different rom (crc32 {:08X}, the rom is {:08X})", self.rom_hash, rom_hash(rom)));
+        }
+        let mut chip8 = Chip8::new(self.mode, self.quirks).with_seed(self.seed);
+        chip8.load_program(rom);
+        Ok(chip8)
+    }
+}
+
+impl fmt::Display for Movie {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} {}", MAGIC, MOVIE_VERSION)?;
+        writeln!(f, "rom {:08X}", self.rom_hash)?;
+        writeln!(f, "seed {}", self.seed)?;
+        writeln!(f, "mode {}", self.mode)?;
+        writeln!(f, "hz {}", self.hz)?;
+        writeln!(f, "frames {}", self.frames)?;
+        writeln!(f, "quirks {}", serde_json::to_string(&self.quirks).unwrap())?;
+        writeln!(f)?;
+        write!(f, "{}", self.input)
+    }
+}
+
+impl FromStr for Movie {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (header, input) = s.split_once("\n\n").ok_or("The movie has no input after its header")?;
+        let mut lines = header.lines();
+
+        let version = lines.next()
+            .and_then(|line| line.strip_prefix(MAGIC))
+            .and_then(|version| version.trim().parse::<u32>().ok())
+            .ok_or("Not a movie")?;
+        if version > MOVIE_VERSION {
+            return Err(format!("The movie has version {}, which is newer than this emulator supports", version));
+        }
+
+        let (mut rom_hash, mut seed, mut mode, mut quirks, mut hz, mut frames) = (None, None, None, None, None, None);
+        for line in lines {
+            let (key, value) = line.split_once(' ').ok_or_else(|| format!("Invalid header line: {}", line))?;
+            let invalid = || format!("Invalid {}: {}", key, value);
+            match key {
+                "rom" => rom_hash = Some(u32::from_str_radix(value, 16).map_err(|_| invalid())?),
+                "seed" => seed = Some(value.parse::<u64>().map_err(|_| invalid())?),
+                "mode" => mode = Some(value.parse::<MachineMode>()?),
+                "quirks" => quirks = Some(serde_json::from_str::<Quirks>(value).map_err(|x| format!("Invalid quirks: {}", x))?),
+                "hz" => hz = Some(value.parse::<u64>().map_err(|_| invalid())?),
+                "frames" => frames = Some(value.parse::<u64>().map_err(|_| invalid())?),
+                // newer versions may add to the header
+                _ => {},
+            }
+        }
+
+        let missing = |key: &str| format!("The movie header has no {}", key);
+        Ok(Self {
+            rom_hash: rom_hash.ok_or_else(|| missing("rom"))?,
+            seed: seed.ok_or_else(|| missing("seed"))?,
+            mode: mode.ok_or_else(|| missing("mode"))?,
+            quirks: quirks.ok_or_else(|| missing("quirks"))?,
+            hz: hz.ok_or_else(|| missing("hz"))?,
+            frames: frames.ok_or_else(|| missing("frames"))?,
+            input: input.parse::<InputScript>().map_err(|x| format!("input {}", x))?,
+        })
+    }
+}
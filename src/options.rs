@@ -2,15 +2,23 @@ use wasm_bindgen::prelude::*;
 use serde::{Deserialize, Serialize};
 use tsify_next::Tsify;
 
+use crate::{chip8::MachineMode, machine::FRAMES_PER_SECOND, quirks::QuirksPreset};
+
 #[derive(Clone, Copy, Tsify, Serialize, Deserialize)]
 #[serde(default)]
 #[tsify(into_wasm_abi, from_wasm_abi)]
 pub struct Options {
     pub invert_colors: bool,
     pub hz: u64,
-    pub fg: RGB,
-    pub bg: RGB,
-    pub vol: f32
+    // instructions run in each 60 Hz frame, overrides hz when set
+    pub cycles_per_frame: Option<u64>,
+    // background, plane 1, plane 2, and both planes
+    pub palette: [RGB; 4],
+    pub vol: f32,
+    pub mode: MachineMode,
+    pub quirks: QuirksPreset,
+    // seeds the random numbers of CXNN so runs can be reproduced, none seeds from the operating system
+    pub seed: Option<u64>,
 }
 
 impl Default for Options {
@@ -18,18 +26,50 @@ impl Default for Options {
         Self {
             invert_colors: false,
             hz: 500,
-            fg: RGB {
-                r: 255,
-                g: 255,
-                b: 255,
-            },
-            bg: RGB {
-                r: 0,
-                g: 0,
-                b: 0,
-            },
-            vol: 1.0
+            cycles_per_frame: None,
+            palette: [
+                RGB {
+                    r: 0,
+                    g: 0,
+                    b: 0,
+                },
+                RGB {
+                    r: 255,
+                    g: 255,
+                    b: 255,
+                },
+                RGB {
+                    r: 170,
+                    g: 170,
+                    b: 170,
+                },
+                RGB {
+                    r: 85,
+                    g: 85,
+                    b: 85,
+                },
+            ],
+            vol: 1.0,
+            mode: MachineMode::default(),
+            quirks: QuirksPreset::default(),
+            seed: None,
+        }
+    }
+}
+
+impl Options {
+    // the speed the machine runs at, in instructions per second
+    pub fn cycles_per_second(&self) -> u64 {
+        self.cycles_per_frame.map_or(self.hz, |cycles| cycles * FRAMES_PER_SECOND)
+    }
+
+    // the palette to render with, inverting colors swaps the background and foreground colors
+    pub fn render_palette(&self) -> [RGB; 4] {
+        let mut palette = self.palette;
+        if self.invert_colors {
+            palette.swap(0, 1);
         }
+        palette
     }
 }
 
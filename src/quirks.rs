@@ -0,0 +1,148 @@
+use std::str::FromStr;
+
+use savefile_derive::Savefile;
+use serde::{Deserialize, Serialize};
+use tsify_next::Tsify;
+
+// What FX55/FX65 do to the index register once the registers have been stored/loaded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Savefile, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoadStoreQuirk {
+    // I is left at the address it started at (SCHIP 1.1, most modern interpreters)
+    Unchanged,
+    // I is incremented by X + 1 (COSMAC VIP, XO-CHIP)
+    Increment,
+    // I is incremented by X (CHIP-48)
+    IncrementByX,
+}
+
+// Behaviour of the opcodes that were interpreted differently across interpreters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Savefile, Serialize, Deserialize)]
+pub struct Quirks {
+    // 8XY1/8XY2/8XY3 reset VF to 0
+    pub vf_reset: bool,
+    // 8XY6/8XYE shift VX in place instead of shifting VY into VX
+    pub shifting: bool,
+    // FX55/FX65 index register behaviour
+    pub load_store: LoadStoreQuirk,
+    // BNNN behaves as BXNN, jumping to XNN + VX instead of NNN + V0
+    pub jumping: bool,
+    // DXYN clips sprites at the edge of the screen instead of wrapping them around
+    pub clipping: bool,
+    // DXYN waits for the vertical blank, so at most one sprite is drawn per 60 Hz frame (COSMAC VIP)
+    pub display_wait: bool,
+    // FX0A returns as soon as a key is held, instead of once it is released again
+    pub key_wait_on_press: bool,
+}
+
+impl Quirks {
+    pub fn vip() -> Self {
+        Self {
+            vf_reset: true,
+            shifting: false,
+            load_store: LoadStoreQuirk::Increment,
+            jumping: false,
+            clipping: true,
+            display_wait: true,
+            key_wait_on_press: false,
+        }
+    }
+
+    pub fn chip48() -> Self {
+        Self {
+            vf_reset: false,
+            shifting: true,
+            load_store: LoadStoreQuirk::IncrementByX,
+            jumping: true,
+            clipping: true,
+            display_wait: false,
+            key_wait_on_press: false,
+        }
+    }
+
+    pub fn schip() -> Self {
+        Self {
+            vf_reset: false,
+            shifting: true,
+            load_store: LoadStoreQuirk::Unchanged,
+            jumping: true,
+            clipping: true,
+            display_wait: false,
+            key_wait_on_press: false,
+        }
+    }
+
+    pub fn xochip() -> Self {
+        Self {
+            vf_reset: false,
+            shifting: false,
+            load_store: LoadStoreQuirk::Increment,
+            jumping: false,
+            clipping: false,
+            display_wait: false,
+            key_wait_on_press: false,
+        }
+    }
+
+    pub fn modern() -> Self {
+        Self {
+            vf_reset: false,
+            shifting: true,
+            load_store: LoadStoreQuirk::Unchanged,
+            jumping: false,
+            clipping: false,
+            display_wait: false,
+            key_wait_on_press: false,
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self::modern()
+    }
+}
+
+// Named quirk profiles, used to select a `Quirks` from the frontends.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Tsify, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+pub enum QuirksPreset {
+    Vip,
+    Chip48,
+    Schip,
+    Xochip,
+    #[default]
+    Modern,
+}
+
+impl QuirksPreset {
+    pub const NAMES: [&'static str; 5] = ["vip", "chip48", "schip", "xochip", "modern"];
+}
+
+impl From<QuirksPreset> for Quirks {
+    fn from(preset: QuirksPreset) -> Self {
+        match preset {
+            QuirksPreset::Vip => Quirks::vip(),
+            QuirksPreset::Chip48 => Quirks::chip48(),
+            QuirksPreset::Schip => Quirks::schip(),
+            QuirksPreset::Xochip => Quirks::xochip(),
+            QuirksPreset::Modern => Quirks::modern(),
+        }
+    }
+}
+
+impl FromStr for QuirksPreset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "vip" => Ok(Self::Vip),
+            "chip48" | "chip-48" => Ok(Self::Chip48),
+            "schip" | "superchip" => Ok(Self::Schip),
+            "xochip" | "xo-chip" => Ok(Self::Xochip),
+            "modern" => Ok(Self::Modern),
+            _ => Err(format!("Unknown quirks preset: {}", s)),
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::VecDeque;
+
+use savefile::SavefileError;
+
+use crate::chip8::Chip8;
+
+// Ring buffer of Chip8 snapshots for rewinding.
+//
+// Only the newest snapshot is kept in full. Every older snapshot is stored as the difference to the
+// snapshot after it: the two are XORed, which leaves zeros wherever nothing changed, and the zero
+// runs are run length encoded. Once the buffer is over its budget the oldest snapshots are dropped.
+pub struct RewindBuffer {
+    // the newest snapshot, serialized with Chip8::save_state
+    latest: Option<Vec<u8>>,
+    // turns a snapshot into the one before it, oldest first
+    deltas: VecDeque<Delta>,
+    // in bytes, the newest snapshot is always kept even if it is larger
+    budget: usize,
+    size: usize,
+}
+
+struct Delta {
+    // the length of the older snapshot, the display and so the snapshots change size with hires
+    len: usize,
+    encoded: Vec<u8>,
+}
+
+impl RewindBuffer {
+    pub fn new(budget: usize) -> Self {
+        Self {
+            latest: None,
+            deltas: VecDeque::new(),
+            budget,
+            size: 0,
+        }
+    }
+
+    // number of snapshots that can be rewound to
+    pub fn len(&self) -> usize {
+        self.latest.as_ref().map_or(0, |_| self.deltas.len() + 1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.latest.is_none()
+    }
+
+    // bytes used by the snapshots
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+        self.size = 0;
+    }
+
+    pub fn push(&mut self, chip8: &mut Chip8) -> Result<(), SavefileError> {
+        let mut snapshot = Vec::new();
+        chip8.save_state(&mut snapshot)?;
+
+        self.size += snapshot.len();
+        if let Some(previous) = self.latest.replace(snapshot) {
+            let delta = Delta {
+                len: previous.len(),
+                encoded: encode(&xor(&previous, self.latest.as_ref().unwrap())),
+            };
+            self.size += delta.encoded.len();
+            self.size -= previous.len();
+            self.deltas.push_back(delta);
+        }
+
+        while self.size > self.budget {
+            let Some(oldest) = self.deltas.pop_front() else {
+                break;
+            };
+            self.size -= oldest.encoded.len();
+        }
+        Ok(())
+    }
+
+    // Restores the newest snapshot and removes it from the buffer, false if the buffer is empty.
+    pub fn rewind(&mut self, chip8: &mut Chip8) -> Result<bool, SavefileError> {
+        let Some(latest) = self.latest.take() else {
+            return Ok(false);
+        };
+        chip8.load_state(&mut latest.as_slice())?;
+
+        self.size -= latest.len();
+        if let Some(delta) = self.deltas.pop_back() {
+            let mut previous = xor(&latest, &decode(&delta.encoded, delta.len.max(latest.len())));
+            previous.truncate(delta.len);
+            self.size -= delta.encoded.len();
+            self.size += previous.len();
+            self.latest = Some(previous);
+        }
+        Ok(true)
+    }
+}
+
+// the shorter slice is padded with zeros
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    (0..a.len().max(b.len()))
+        .map(|index| a.get(index).unwrap_or(&0) ^ b.get(index).unwrap_or(&0))
+        .collect()
+}
+
+// A run of zeros and a run of literal bytes, repeated. Both lengths are LEB128 encoded.
+pub fn encode(delta: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    let mut index = 0;
+    while index < delta.len() {
+        let zeros = delta[index..].iter().take_while(|&&byte| byte == 0).count();
+        index += zeros;
+        let literals = delta[index..].iter().take_while(|&&byte| byte != 0).count();
+        write_length(&mut encoded, zeros);
+        write_length(&mut encoded, literals);
+        encoded.extend_from_slice(&delta[index..index + literals]);
+        index += literals;
+    }
+    encoded
+}
+
+// the decoded delta is padded with zeros to len bytes
+pub fn decode(encoded: &[u8], len: usize) -> Vec<u8> {
+    let mut delta = Vec::with_capacity(len);
+    let mut index = 0;
+    while index < encoded.len() {
+        let zeros = read_length(encoded, &mut index);
+        let literals = read_length(encoded, &mut index);
+        delta.resize(delta.len() + zeros, 0);
+        delta.extend_from_slice(&encoded[index..index + literals]);
+        index += literals;
+    }
+    delta.resize(len, 0);
+    delta
+}
+
+fn write_length(encoded: &mut Vec<u8>, mut length: usize) {
+    loop {
+        let byte = (length & 0x7F) as u8;
+        length >>= 7;
+        if length == 0 {
+            encoded.push(byte);
+            return;
+        }
+        encoded.push(byte | 0x80);
+    }
+}
+
+fn read_length(encoded: &[u8], index: &mut usize) -> usize {
+    let mut length = 0;
+    let mut shift = 0;
+    loop {
+        let byte = encoded[*index];
+        *index += 1;
+        length |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return length;
+        }
+        shift += 7;
+    }
+}
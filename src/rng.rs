@@ -0,0 +1,40 @@
+use rand::RngCore;
+use savefile_derive::Savefile;
+use serde::{Deserialize, Serialize};
+
+// xorshift64* generator for CXNN. Its state is part of Chip8, so save states and
+// re-executing from a snapshot give the same random numbers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Savefile, Serialize, Deserialize)]
+pub struct Xorshift {
+    state: u64,
+}
+
+impl Xorshift {
+    pub fn new(seed: u64) -> Self {
+        // splitmix64 spreads small seeds over the whole state, xorshift gets stuck on a state of 0
+        let mut z = seed.wrapping_add(0x9E3779B97F4A7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^= z >> 31;
+        Self { state: if z == 0 { 0x9E3779B97F4A7C15 } else { z } }
+    }
+
+    // seeded from the operating system, for runs that do not have to be reproducible
+    pub fn from_entropy() -> Self {
+        Self::new(rand::thread_rng().next_u64())
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(0x2545F4914F6CDD1D)
+    }
+
+    pub fn next_u8(&mut self) -> u8 {
+        // the high bits are the best distributed
+        (self.next_u64() >> 56) as u8
+    }
+}
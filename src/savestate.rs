@@ -0,0 +1,177 @@
+use std::io::{Read, Write};
+
+use savefile::{load, save};
+use savefile_derive::Savefile;
+
+use crate::{chip8::{Chip8, MachineMode, LORES_HEIGHT, LORES_WIDTH}, errors::SaveStateError, options::Options, quirks::Quirks};
+
+// Save state files.
+//
+// A file starts with MAGIC and the format version as a little endian u32, followed by a savefile dump
+// of SaveState written at that version. The machine itself is kept as a nested Chip8::save_state dump,
+// so fields added to Chip8 are versioned there with #[savefile_versions] and STATE_VERSION.
+//
+// Format versions:
+// 0 - no container, a raw savefile dump of the original 64x32 Chip8
+// 1 - the container below
+pub const MAGIC: &[u8; 8] = b"CHIP8SAV";
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Savefile)]
+pub struct SaveState {
+    // crc32 of the rom the state was saved with, none for states migrated from version 0
+    pub rom_hash: Option<u32>,
+    // seconds since the unix epoch
+    pub timestamp: u64,
+    // the Options the emulator was running with as json, none for states migrated from version 0 or imported from json
+    pub options: Option<String>,
+    pub thumbnail: Thumbnail,
+    // serialized with Chip8::save_state
+    pub state: Vec<u8>,
+}
+
+// The display at the time of saving, scaled to 64x32. One palette index per pixel, like Chip8::display.
+#[derive(Savefile)]
+pub struct Thumbnail {
+    pub width: u16,
+    pub height: u16,
+    pub pixels: Vec<u8>,
+}
+
+// crc32 of a rom, identifies the rom a state or recording belongs to
+pub fn rom_hash(rom: &[u8]) -> u32 {
+    crc32fast::hash(rom)
+}
+
+impl SaveState {
+    pub fn capture(chip8: &mut Chip8, rom_hash: Option<u32>, options: Option<&Options>) -> Result<Self, SaveStateError> {
+        let mut state = Vec::new();
+        chip8.save_state(&mut state)?;
+        Ok(Self {
+            rom_hash,
+            timestamp: unix_time(),
+            options: options.map(|options| serde_json::to_string(options).unwrap()),
+            thumbnail: Thumbnail::of(chip8),
+            state,
+        })
+    }
+
+    pub fn write(&self, writer: &mut dyn Write) -> Result<(), SaveStateError> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        save(writer, FORMAT_VERSION, self)?;
+        Ok(())
+    }
+
+    // Reads a state of any format version, migrating older ones.
+    pub fn read(reader: &mut dyn Read) -> Result<Self, SaveStateError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+
+        let Some(rest) = bytes.strip_prefix(MAGIC) else {
+            return migrate_v0(&bytes);
+        };
+        if rest.len() < 4 {
+            return Err(SaveStateError::Truncated);
+        }
+        let version = u32::from_le_bytes(rest[..4].try_into().unwrap());
+        if version > FORMAT_VERSION {
+            return Err(SaveStateError::UnsupportedVersion { version });
+        }
+        Ok(load(&mut &rest[4..], version)?)
+    }
+
+    // fails if the state was saved with a different rom, states that do not know their rom are accepted
+    pub fn check_rom(&self, rom_hash: u32) -> Result<(), SaveStateError> {
+        match self.rom_hash {
+            Some(state) if state != rom_hash => Err(SaveStateError::WrongRom { state, rom: rom_hash }),
+            _ => Ok(()),
+        }
+    }
+
+    // the options the state was saved with
+    pub fn options(&self) -> Option<Options> {
+        serde_json::from_str(self.options.as_ref()?).ok()
+    }
+
+    // loads the machine, watchpoints stay set like with Chip8::load_state
+    pub fn restore(&self, chip8: &mut Chip8) -> Result<(), SaveStateError> {
+        chip8.load_state(&mut self.state.as_slice())?;
+        Ok(())
+    }
+}
+
+impl Thumbnail {
+    pub fn of(chip8: &Chip8) -> Self {
+        // hires is scaled down by combining the planes of each 2x2 block
+        let scale = chip8.display_width() / LORES_WIDTH;
+        let mut pixels = vec![0; LORES_WIDTH * LORES_HEIGHT];
+        for (index, pixel) in chip8.display.iter().enumerate() {
+            let (x, y) = (index % chip8.display_width() / scale, index / chip8.display_width() / scale);
+            pixels[y * LORES_WIDTH + x] |= pixel;
+        }
+        Self { width: LORES_WIDTH as u16, height: LORES_HEIGHT as u16, pixels }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn unix_time() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |time| time.as_secs())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn unix_time() -> u64 {
+    (js_sys::Date::now() / 1000.0) as u64
+}
+
+// The original 64x32 machine, before quirks, modes and SCHIP. Saved by the binary with savefile
+// version 0 and by Chip8::save_state with version 1, both with the same layout.
+mod v0 {
+    use savefile_derive::Savefile;
+
+    #[derive(Savefile)]
+    pub struct Chip8 {
+        pub opcode: u16,
+        pub memory: [u8; 4096],
+        pub v_registers: [u8; 16],
+        pub i: u16,
+        pub pc: u16,
+        pub delay_timer: u8,
+        pub sound_timer: u8,
+        pub jump_stack: [u16; 16],
+        pub stack_pointer: u16,
+        pub key_state: [u8; 16],
+        pub display: [u8; 2048],
+    }
+}
+
+fn migrate_v0(bytes: &[u8]) -> Result<SaveState, SaveStateError> {
+    let old = load::<v0::Chip8>(&mut &bytes[..], 1).map_err(SaveStateError::NotAState)?;
+
+    // everything added since is left at its default
+    let mut chip8 = Chip8::new(MachineMode::Chip8, Quirks::default());
+    chip8.opcode = old.opcode;
+    chip8.memory.copy_from_slice(&old.memory);
+    chip8.v_registers = old.v_registers;
+    chip8.i = old.i;
+    chip8.pc = old.pc;
+    chip8.delay_timer = old.delay_timer;
+    chip8.sound_timer = old.sound_timer;
+    // the old stack pointer was incremented before a push, so the return addresses were at 1..=stack_pointer,
+    // they move down one level to the stack that points at the next free level
+    let depth = (old.stack_pointer as usize).min(old.jump_stack.len() - 1);
+    chip8.jump_stack[..depth].copy_from_slice(&old.jump_stack[1..=depth]);
+    chip8.stack_pointer = depth as u16;
+    chip8.key_state = old.key_state;
+    chip8.display.copy_from_slice(&old.display);
+
+    let mut state = Vec::new();
+    chip8.save_state(&mut state)?;
+    Ok(SaveState {
+        rom_hash: None,
+        timestamp: 0,
+        options: None,
+        thumbnail: Thumbnail::of(&chip8),
+        state,
+    })
+}
@@ -0,0 +1,120 @@
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::chip8::Chip8;
+use crate::disasm::{mnemonic, Syntax};
+use crate::instruction::decode;
+
+// Execution traces, one record per executed instruction with the state before it executed.
+//
+// The text format is stable, so traces of different emulators can be diffed. Every line is
+//
+//     CCCCCCCCCC PPPP OOOO V=XX XX XX XX XX XX XX XX XX XX XX XX XX XX XX XX I=IIII SP=SS DT=DD ST=TT disassembly
+//
+// - C is the decimal cycle number, counted from 0 when tracing started
+// - P is the pc of the instruction and O its opcode, F000 NNNN only shows F000
+// - XX are V0 to VF, I the index register, SS the stack pointer, DD the delay timer and TT the sound timer
+// - all numbers but the cycle are upper case hex, zero padded to the widths shown
+// - the disassembly is in Octo syntax, and is the only part of a line that may contain spaces
+//
+// The binary format is a header of the 4 bytes `C8TR`, a format version byte (1) and 3 zero bytes,
+// followed by a 25 byte record per instruction, the cycle number is the index of the record.
+// All words are big endian:
+//
+//     pc: u16, opcode: u16, v: [u8; 16], i: u16, sp: u8, dt: u8, st: u8
+
+pub const BINARY_MAGIC: [u8; 4] = *b"C8TR";
+pub const BINARY_VERSION: u8 = 1;
+pub const BINARY_RECORD_SIZE: usize = 25;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TraceFormat {
+    #[default]
+    Text,
+    Binary,
+}
+
+impl TraceFormat {
+    pub const NAMES: [&'static str; 2] = ["text", "binary"];
+}
+
+impl FromStr for TraceFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "binary" => Ok(Self::Binary),
+            _ => Err(format!("Unknown trace format: {}", s)),
+        }
+    }
+}
+
+pub struct Tracer {
+    writer: BufWriter<Box<dyn Write + Send>>,
+    format: TraceFormat,
+    cycle: u64,
+}
+
+impl Tracer {
+    pub fn new(writer: Box<dyn Write + Send>, format: TraceFormat) -> io::Result<Self> {
+        let mut writer = BufWriter::new(writer);
+        if format == TraceFormat::Binary {
+            writer.write_all(&BINARY_MAGIC)?;
+            writer.write_all(&[BINARY_VERSION, 0, 0, 0])?;
+        }
+        Ok(Self { writer, format, cycle: 0 })
+    }
+
+    pub fn create(path: &Path, format: TraceFormat) -> io::Result<Self> {
+        Self::new(Box::new(File::create(path)?), format)
+    }
+
+    // Records the instruction at pc, call it right before `Chip8::single_cycle`.
+    // Nothing is recorded for halted machines or a pc outside of memory, as no instruction executes.
+    pub fn record(&mut self, chip8: &Chip8) -> io::Result<()> {
+        let Some(opcode) = chip8.peek_word(chip8.pc) else {
+            return Ok(());
+        };
+        if chip8.halted {
+            return Ok(());
+        }
+
+        match self.format {
+            TraceFormat::Text => writeln!(self.writer, "{}", text_line(self.cycle, opcode, chip8))?,
+            TraceFormat::Binary => {
+                let mut record = [0; BINARY_RECORD_SIZE];
+                record[0..2].copy_from_slice(&chip8.pc.to_be_bytes());
+                record[2..4].copy_from_slice(&opcode.to_be_bytes());
+                record[4..20].copy_from_slice(&chip8.v_registers);
+                record[20..22].copy_from_slice(&chip8.i.to_be_bytes());
+                record[22] = chip8.stack_pointer as u8;
+                record[23] = chip8.delay_timer;
+                record[24] = chip8.sound_timer;
+                self.writer.write_all(&record)?;
+            },
+        }
+        self.cycle += 1;
+        Ok(())
+    }
+
+    // the frontends flush once per frame, so little is lost when the process exits
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+fn text_line(cycle: u64, opcode: u16, chip8: &Chip8) -> String {
+    let registers = chip8.v_registers.iter().map(|value| format!("{:02X}", value)).collect::<Vec<_>>().join(" ");
+    let disassembly = match decode(opcode) {
+        Some(instruction) => mnemonic(&instruction, Syntax::Octo, chip8.peek_word(chip8.pc.wrapping_add(2)), &BTreeSet::new()),
+        None => "unknown".to_string(),
+    };
+    format!(
+        "{:010} {:04X} {:04X} V={} I={:04X} SP={:02X} DT={:02X} ST={:02X} {}",
+        cycle, chip8.pc, opcode, registers, chip8.i, chip8.stack_pointer, chip8.delay_timer, chip8.sound_timer, disassembly
+    )
+}
@@ -1,10 +1,82 @@
-pub fn render_texture_to_target(dispmem: &[u8; 2048], frame: &mut [u8], fg: &crate::options::RGB, bg: &crate::options::RGB) {
+use pixels::Pixels;
+
+use crate::{chip8::{Chip8, FONTSET, LORES_WIDTH}, options::RGB, savestate::Thumbnail};
+
+// palette used to render the display once a rom has crashed
+pub static CRASH_PALETTE: [RGB; 4] = [
+    RGB { r: 96, g: 0, b: 0 },
+    RGB { r: 255, g: 255, b: 255 },
+    RGB { r: 255, g: 128, b: 128 },
+    RGB { r: 192, g: 64, b: 64 },
+];
+
+// each pixel of the display is an index into the palette
+pub fn render_texture_to_target(dispmem: &[u8], frame: &mut [u8], palette: &[RGB; 4]) {
     for (i, pixel) in frame.chunks_exact_mut(4).enumerate() {
-        if dispmem[i] == 1 {
-            pixel.copy_from_slice(&[fg.r, fg.g, fg.b, 0xff]);
+        let color = &palette[(dispmem[i] & 0b11) as usize];
+        pixel.copy_from_slice(&[color.r, color.g, color.b, 0xff]);
+    }
+}
+
+// characters for the four pixel values, the background is not blank so the size of the display shows
+pub static ASCII_PIXELS: [char; 4] = ['.', '#', '+', '@'];
+
+// the display as text, a line per row
+pub fn render_ascii(chip8: &Chip8) -> String {
+    let mut ascii = String::with_capacity((chip8.display_width() + 1) * chip8.display_height());
+    for row in chip8.display.chunks_exact(chip8.display_width()) {
+        ascii.extend(row.iter().map(|&pixel| ASCII_PIXELS[(pixel & 0b11) as usize]));
+        ascii.push('\n');
+    }
+    ascii
+}
+
+// resizes the pixel buffer when the display has switched between lores and hires
+pub fn resize_target_to_display(pixels: &mut Pixels, chip8: &Chip8) {
+    let width = chip8.display_width();
+    let height = chip8.display_height();
+    if pixels.frame().len() != width * height * 4 {
+        pixels.resize_buffer(width as u32, height as u32).unwrap();
+    }
+}
+
+// Draws the save slot picker over a display: a box in the top left corner with the slot number and the
+// thumbnail of the state in it, or nothing under the number if the slot is empty. The layout is in lores
+// pixels and scaled up on the hires display.
+pub fn draw_slot_overlay(display: &mut [u8], width: usize, slot: u8, thumbnail: Option<&Thumbnail>) {
+    let scale = width / LORES_WIDTH;
+    let mut fill = |x: usize, y: usize, w: usize, h: usize, value: u8| {
+        for row in y * scale..(y + h) * scale {
+            display[row * width + x * scale..row * width + (x + w) * scale].fill(value);
         }
-        else {
-            pixel.copy_from_slice(&[bg.r, bg.g, bg.b, 0xff]);
+    };
+
+    // a 36x25 box with a border, the number in the font at the top and the thumbnail at half size below it
+    fill(1, 1, 36, 25, 1);
+    fill(2, 2, 34, 23, 0);
+    for (row, bits) in FONTSET[slot as usize * 5..][..5].iter().enumerate() {
+        for col in 0..4 {
+            if bits & (0x80 >> col) != 0 {
+                fill(3 + col, 3 + row, 1, 1, 1);
+            }
+        }
+    }
+
+    let Some(thumbnail) = thumbnail else {
+        return;
+    };
+    // every pixel drawn combines the planes of a block of thumbnail pixels
+    let (thumb_width, thumb_height) = (32 * scale, 16 * scale);
+    let block = (thumbnail.width as usize / thumb_width).max(1);
+    for y in 0..thumb_height {
+        for x in 0..thumb_width {
+            let mut pixel = 0;
+            for by in 0..block {
+                for bx in 0..block {
+                    pixel |= thumbnail.pixels.get((y * block + by) * thumbnail.width as usize + x * block + bx).copied().unwrap_or(0);
+                }
+            }
+            display[(9 * scale + y) * width + 3 * scale + x] = pixel;
         }
     }
-}
\ No newline at end of file
+}
@@ -1,9 +1,9 @@
 
 use wasm_bindgen::prelude::*;
 use web_sys::Element;
-use std::sync::{mpsc::{channel, Sender}, Arc, Mutex, RwLock};
+use std::{sync::{mpsc::{channel, Sender}, Arc, Mutex, RwLock}, time::Duration};
 
-use crate::{audio::Beeper, chip8::Chip8, input::{parse_input, KEYMAP}, options::{Options, RGB}, utils::render_texture_to_target};
+use crate::{audio::{AudioSink, Beeper, Silence}, chip8::{Chip8, LORES_HEIGHT, LORES_WIDTH}, errors::Chip8Error, input::{parse_input, KEYMAP}, machine::{Clock, Machine}, options::{Options, RGB}, quirks::Quirks, rng::Xorshift, utils::{render_texture_to_target, resize_target_to_display, CRASH_PALETTE}};
 use pixels::{Pixels, SurfaceTexture};
 use winit::{
     event::{Event, WindowEvent}, event_loop::{ControlFlow, EventLoop}, platform::web::{EventLoopExtWebSys, WindowExtWebSys}, window::{Window, WindowBuilder}
@@ -17,16 +17,23 @@ pub fn init() {
     console_log::init_with_level(log::Level::Trace).expect("error initializing logger");
 }
 
+// std::time::Instant is not available on the web
+struct DateClock;
+
+impl Clock for DateClock {
+    fn now(&self) -> Duration {
+        Duration::from_secs_f64(js_sys::Date::now() / 1000.0)
+    }
+}
+
 struct WasmEventLoopOptions {
-    fg: RGB,
-    bg: RGB,
+    palette: [RGB; 4],
 }
 
 impl From<Options> for WasmEventLoopOptions {
     fn from(options: Options) -> Self {
         Self {
-            bg: options.bg,
-            fg: options.fg
+            palette: options.render_palette(),
         }
     }
 }
@@ -74,7 +81,7 @@ impl WasmEventLoop {
                             let pixels_main_loop_wrapper = future_main_loop_wrapper.clone();
                             wasm_bindgen_futures::spawn_local(async move {
                                 let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
-                                let pixels = Pixels::new_async(64, 32, surface_texture).await.unwrap();
+                                let pixels = Pixels::new_async(LORES_WIDTH as u32, LORES_HEIGHT as u32, surface_texture).await.unwrap();
                                 *pixels_main_loop_wrapper.lock().unwrap() = Some(WasmMainLoopWrapper {
                                     main_loop,
                                     pixels,
@@ -107,7 +114,17 @@ impl WasmEventLoop {
                     let window = &mut main_loop_wrapper.window;
                     match ev {
                         Event::RedrawRequested(_) => {
-                            render_texture_to_target(&main_loop.chip8.read().unwrap().display, pixels.frame_mut(), &main_loop.event_loop_options.fg, &main_loop.event_loop_options.bg);
+                            let machine = main_loop.machine.lock().unwrap();
+                            let chip8 = &machine.chip8;
+                            resize_target_to_display(pixels, chip8);
+                            // crash screen, the last frame is shown with the crash palette
+                            let palette = if main_loop.crash.read().unwrap().is_some() {
+                                &CRASH_PALETTE
+                            }
+                            else {
+                                &main_loop.event_loop_options.palette
+                            };
+                            render_texture_to_target(&chip8.display, pixels.frame_mut(), palette);
                             pixels.render().unwrap();
                         }
                         Event::WindowEvent { window_id: _, event: ref window_ev } => match window_ev {
@@ -185,17 +202,17 @@ struct WasmMainLoopWrapper {
 }
 
 struct WasmMainLoopOptions {
-    invert_colors: bool,
     hz: u64,
     vol: f32,
+    quirks: Quirks,
 }
 
 impl From<Options> for WasmMainLoopOptions {
     fn from(options: Options) -> Self {
         Self {
-            invert_colors: options.invert_colors,
-            hz: options.hz,
+            hz: options.cycles_per_second(),
             vol: options.vol,
+            quirks: options.quirks.into(),
         }
     }
 }
@@ -209,7 +226,9 @@ enum WasmMainLoopMessage {
 #[wasm_bindgen]
 pub struct WasmMainLoop {
     tx: Sender<WasmMainLoopMessage>,
-    chip8: Arc<RwLock<Chip8>>,
+    machine: Arc<Mutex<Machine>>,
+    // set when the rom crashes, the main loop stops once it is set
+    crash: Arc<RwLock<Option<Chip8Error>>>,
     parent: Element,
     event_loop_options: WasmEventLoopOptions,
 }
@@ -219,99 +238,74 @@ impl WasmMainLoop {
     pub async fn create(parent: Element, rom: &[u8], options: Options) -> Self {
         let mut main_loop_options = WasmMainLoopOptions::from(options);
         // setup cpu instance
-        let mut chip8_inst = Chip8::default();
-        let mut invert_colors: bool = main_loop_options.invert_colors;
-        chip8_inst.display = [invert_colors as u8; 2048];
+        let mut chip8_inst = Chip8::new(options.mode, main_loop_options.quirks);
+        if let Some(seed) = options.seed {
+            chip8_inst.rng = Xorshift::new(seed);
+        }
     
         // load rom/state into chip8inst
         chip8_inst.load_program(rom);
+
+        // the stream stays in the main loop, the machine only gets a sink that shares the sound with it
+        let mut beeper = Beeper::new(main_loop_options.vol);
+        if beeper.is_err() {
+            log!("Audio not initialized!");
+        }
+        let audio: Box<dyn AudioSink + Send> = match &beeper {
+            Ok(beeper) => Box::new(beeper.sink()),
+            Err(_) => Box::new(Silence),
+        };
+        let machine_arc = Arc::new(Mutex::new(Machine::new(chip8_inst, main_loop_options.hz, Box::new(DateClock), audio)));
     
-        let chip8_arc = Arc::new(RwLock::new(chip8_inst));
-    
-        let main_loop_chip8 = chip8_arc.clone();
+        let crash_arc: Arc<RwLock<Option<Chip8Error>>> = Arc::new(RwLock::new(None));
+
+        let main_loop_machine = machine_arc.clone();
+        let main_loop_crash = crash_arc.clone();
         let (tx, rx) = channel::<WasmMainLoopMessage>();
         wasm_bindgen_futures::spawn_local(async move {
-            let mut beeper = Beeper::new(main_loop_options.vol);
-            if !beeper.is_err() {
-                log!("Audio not initialized!");
-            }
-    
-            let mut run_times = 0;
-            loop {
-                let run_hz:  u64 = main_loop_options.hz;
-                let delay: u64 = 1000 / run_hz;
-                let next_frame_time = js_sys::Date::now() as u64 + delay;
-                let satisfied_run_times: u64 = (1000 / 60) / delay;
-
+            'main_loop: loop {
                 if let Ok(beeper) = beeper.as_mut() {
                     beeper.set_vol(main_loop_options.vol).unwrap();
                 }
-                if main_loop_options.invert_colors != invert_colors {
-                    invert_colors = main_loop_options.invert_colors;
-                    main_loop_chip8
-                        .write()
-                        .unwrap()
-                        .display
-                        .iter_mut()
-                        .for_each(|x| {
-                            if *x > 0 {
-                                *x = 0;
-                            }
-                            else {
-                                *x = 1;
-                            }
-                        });
-                }
 
                 for mesg in rx.try_iter() {
                     match mesg {
-                        WasmMainLoopMessage::Stop => break,
+                        WasmMainLoopMessage::Stop => {
+                            main_loop_machine.lock().unwrap().silence();
+                            break 'main_loop;
+                        },
                         WasmMainLoopMessage::SetOptions(mesg) => {
+                            let mut machine = main_loop_machine.lock().unwrap();
+                            machine.chip8.quirks = mesg.quirks;
+                            machine.set_hz(mesg.hz);
                             main_loop_options = mesg;
                         },
                         WasmMainLoopMessage::SetKey(key, pressed) => {
                             if !KEYMAP.contains(&(key as usize)) {
                                 continue;
                             }
-                            main_loop_chip8.write().unwrap().key_state[key] = pressed as u8;
+                            main_loop_machine.lock().unwrap().chip8.key_state[key] = pressed as u8;
                         
                         }
                     }
                 }
-                
-                // timer stuff
-                if run_times >= satisfied_run_times {
-                    if main_loop_chip8.read().unwrap().delay_timer > 0 {
-                        main_loop_chip8.write().unwrap().delay_timer -= 1;
-                    }
-                    if main_loop_chip8.read().unwrap().sound_timer > 0 {
-                        if beeper.is_ok() && main_loop_options.vol > 0.0 {
-                            beeper.as_ref().unwrap().play();
-                        }
-                        main_loop_chip8.write().unwrap().sound_timer -= 1;
-                    }
-                    else if beeper.is_ok() && main_loop_options.vol > 0.0 {
-                        beeper.as_ref().unwrap().pause();
-                    }
-    
-                    run_times = 0;
-                }
-                run_times += 1;
-    
-    
-                // cycle cpu
-                main_loop_chip8.write().unwrap().single_cycle();
-    
-                let final_date = js_sys::Date::now() as u64;
-                if next_frame_time > final_date {
-                    TimeoutFuture::new((next_frame_time - final_date) as u32).await;
+
+                let mut machine = main_loop_machine.lock().unwrap();
+                if let Err(err) = machine.run_due() {
+                    log!(err.to_string());
+                    *main_loop_crash.write().unwrap() = Some(err);
+                    break;
                 }
+                let sleep = machine.until_next_frame();
+                drop(machine);
+                TimeoutFuture::new(sleep.as_millis() as u32).await;
             }
         });
 
         Self {
             tx,
-            chip8: chip8_arc,
+            machine: machine_arc,
+            crash: crash_arc,
             parent,
             event_loop_options: WasmEventLoopOptions::from(options),
         }
@@ -321,6 +315,11 @@ impl WasmMainLoop {
         self.tx.send(WasmMainLoopMessage::Stop).unwrap();
     }
 
+    // the error the rom crashed with, if it has crashed
+    pub fn crash_message(&self) -> Option<String> {
+        self.crash.read().unwrap().map(|err| err.to_string())
+    }
+
     pub fn set_options(&mut self, options: Options) {
         self.event_loop_options = WasmEventLoopOptions::from(options);
         self.tx.send(WasmMainLoopMessage::SetOptions(WasmMainLoopOptions::from(options))).unwrap();
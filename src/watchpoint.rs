@@ -0,0 +1,67 @@
+use std::fmt::{self, Display};
+
+// Which memory accesses a watchpoint fires on, pointing I into the range always fires.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+// Watches the addresses from start to end, both inclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub start: u16,
+    pub end: u16,
+    pub kind: WatchKind,
+}
+
+impl Watchpoint {
+    pub fn contains(&self, address: usize) -> bool {
+        (self.start as usize..=self.end as usize).contains(&address)
+    }
+
+    fn watches(&self, access: Access) -> bool {
+        match access {
+            Access::Read => self.kind != WatchKind::Write,
+            Access::Write => self.kind != WatchKind::Read,
+            Access::Index => true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    // ANNN, FX1E or F000 NNNN set I to an address in the range
+    Index,
+}
+
+// A watched access, old and new are the bytes before and after a read or write,
+// or the values of I before and after an index change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WatchHit {
+    pub pc: u16,
+    pub opcode: u16,
+    pub access: Access,
+    pub address: u16,
+    pub old: u16,
+    pub new: u16,
+}
+
+impl Display for WatchHit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.access {
+            Access::Read => write!(f, "Read {:#05X} = {:02X}", self.address, self.new)?,
+            Access::Write => write!(f, "Write {:#05X} {:02X} -> {:02X}", self.address, self.old, self.new)?,
+            Access::Index => write!(f, "I {:#05X} -> {:#05X}", self.old, self.new)?,
+        }
+        write!(f, " at {:#05X} executing {:04X}", self.pc, self.opcode)
+    }
+}
+
+// true if any of the watchpoints fires on this access
+pub fn is_watched(watchpoints: &[Watchpoint], address: usize, access: Access) -> bool {
+    watchpoints.iter().any(|watchpoint| watchpoint.watches(access) && watchpoint.contains(address))
+}
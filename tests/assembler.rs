@@ -0,0 +1,58 @@
+use std::path::Path;
+
+use chip_8_emu::assembler::{assemble, assemble_file};
+use chip_8_emu::chip8::{Chip8, StepOutcome};
+use chip_8_emu::errors::AssembleError;
+
+mod common;
+use common::fixture;
+
+fn assert_assembles_to(source: &str, rom: &str) {
+    let assembled = assemble_file(&fixture("assembler", source)).unwrap();
+    let expected = std::fs::read(fixture("assembler", rom)).unwrap();
+    assert_eq!(assembled, expected);
+}
+
+#[test]
+fn assembles_labels_and_data() {
+    assert_assembles_to("box.asm", "box.ch8");
+}
+
+#[test]
+fn assembles_includes_constants_and_expressions() {
+    assert_assembles_to("include.asm", "include.ch8");
+}
+
+#[test]
+fn assembled_rom_runs() {
+    let rom = assemble_file(&fixture("assembler", "box.asm")).unwrap();
+    let mut chip8 = Chip8::default();
+    chip8.load_program(&rom);
+    for _ in 0..8 {
+        assert_eq!(chip8.single_cycle(), Ok(StepOutcome::Executed));
+    }
+    // the box ends up drawn at 30, 13 and the program loops on its last jump
+    assert_eq!(chip8.pc, 0x20A);
+    assert_eq!(chip8.display[13 * 64 + 30], 1);
+}
+
+#[test]
+fn reports_the_line_of_an_undefined_label() {
+    let source = "CLS\n  JP nowhere ; comment\n";
+    match assemble(source, Path::new("test.asm")) {
+        Err(AssembleError::Syntax { file, line, message }) => {
+            assert_eq!(file, "test.asm");
+            assert_eq!(line, 2);
+            assert_eq!(message, "Undefined symbol nowhere");
+        },
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn rejects_out_of_range_values() {
+    assert!(assemble("LD V0, 256", Path::new("test.asm")).is_err());
+    assert!(assemble("DRW V0, V1, 16", Path::new("test.asm")).is_err());
+    assert!(assemble("JP #1000", Path::new("test.asm")).is_err());
+    assert!(assemble("a: CLS\na: CLS", Path::new("test.asm")).is_err());
+}
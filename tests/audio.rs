@@ -0,0 +1,39 @@
+use chip_8_emu::audio::Sound;
+use chip_8_emu::chip8::{Chip8, MachineMode};
+use chip_8_emu::quirks::Quirks;
+
+fn assert_close(actual: f32, expected: f32) {
+    assert!((actual - expected).abs() < expected / 100_000.0, "{} is not {}", actual, expected);
+}
+
+#[test]
+fn pattern_rate_is_4000_hz_at_the_default_pitch() {
+    assert_close(Sound::pattern_rate(64), 4000.0);
+}
+
+#[test]
+fn pattern_rate_doubles_every_48_pitches() {
+    assert_close(Sound::pattern_rate(112), 8000.0);
+    assert_close(Sound::pattern_rate(16), 2000.0);
+    // 4000 * 2^((255 - 64) / 48)
+    assert_close(Sound::pattern_rate(255), 63082.44);
+}
+
+#[test]
+fn plays_the_buzzer_without_a_pattern() {
+    for mode in [MachineMode::Chip8, MachineMode::Xochip] {
+        assert_eq!(Chip8::new(mode, Quirks::modern()).sound(), Sound::Buzzer, "{:?}", mode);
+    }
+}
+
+#[test]
+fn plays_the_pattern_only_in_xochip_mode() {
+    let pattern = [0xF0; 16];
+    let mut chip8 = Chip8::new(MachineMode::Xochip, Quirks::modern());
+    chip8.audio_pattern = Some(pattern);
+    chip8.pitch = 80;
+    assert_eq!(chip8.sound(), Sound::Pattern { pattern, pitch: 80 });
+
+    chip8.mode = MachineMode::Chip8;
+    assert_eq!(chip8.sound(), Sound::Buzzer);
+}
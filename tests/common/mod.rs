@@ -0,0 +1,18 @@
+// Helpers shared by the integration tests, every test crate only uses some of them.
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+
+use chip_8_emu::chip8::Chip8;
+
+// a file in tests/fixtures/<area>
+pub fn fixture(area: &str, name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(area).join(name)
+}
+
+// the save state of the machine, two machines are in the same state when their snapshots are equal
+pub fn snapshot(chip8: &mut Chip8) -> Vec<u8> {
+    let mut state = Vec::new();
+    chip8.save_state(&mut state).unwrap();
+    state
+}
@@ -0,0 +1,129 @@
+use chip_8_emu::assembler::assemble_file;
+use chip_8_emu::audio::Silence;
+use chip_8_emu::chip8::{Chip8, MachineMode, StepOutcome};
+use chip_8_emu::machine::{InputScript, Machine, VirtualClock, FRAMES_PER_SECOND};
+use chip_8_emu::quirks::Quirks;
+use chip_8_emu::utils::render_ascii;
+
+mod common;
+use common::fixture;
+
+// A rom that is run headlessly for a fixed number of frames, and then compared with the golden image
+// of its display. Golden images are the display as text, in a file next to the rom with a .txt extension.
+struct Case {
+    // in tests/fixtures/conformance, roms ending in .asm are assembled first
+    rom: &'static str,
+    mode: MachineMode,
+    quirks: Quirks,
+    frames: u64,
+    cycles_per_frame: u64,
+    // keys held down while it runs, see InputScript
+    input: &'static str,
+    // written to memory after the rom is loaded, test roms read their options from there
+    poke: &'static [(u16, u8)],
+}
+
+impl Case {
+    fn new(rom: &'static str) -> Self {
+        Self {
+            rom,
+            mode: MachineMode::Chip8,
+            quirks: Quirks::modern(),
+            frames: 120,
+            cycles_per_frame: 30,
+            input: "",
+            poke: &[],
+        }
+    }
+}
+
+fn check(case: Case) {
+    let path = fixture("conformance", case.rom);
+    // the golden image is checked for first, a missing one fails whether or not the rom is there
+    let golden = path.with_extension("txt");
+    let expected = std::fs::read_to_string(&golden).unwrap_or_else(|x| panic!("Could not read {}: {}", golden.display(), x));
+    let rom = if path.extension().is_some_and(|extension| extension == "asm") {
+        assemble_file(&path).unwrap()
+    }
+    else {
+        std::fs::read(&path).unwrap_or_else(|x| panic!("Could not read {}: {}, see tests/fixtures/conformance/README.md", path.display(), x))
+    };
+
+    let mut chip8 = Chip8::new(case.mode, case.quirks);
+    chip8.load_program(&rom);
+    for &(address, value) in case.poke {
+        chip8.memory[address as usize] = value;
+    }
+    let input = case.input.parse::<InputScript>().unwrap();
+    let mut machine = Machine::new(chip8, case.cycles_per_frame * FRAMES_PER_SECOND, Box::new(VirtualClock::new()), Box::new(Silence));
+    while machine.frames() < case.frames {
+        if let Some(keys) = input.keys_at(machine.frames()) {
+            machine.chip8.key_state = keys;
+        }
+        match machine.run_frame() {
+            Ok(StepOutcome::Halted) => break,
+            Ok(_) => {},
+            Err(err) => panic!("{} crashed: {}", case.rom, err),
+        }
+    }
+
+    let actual = render_ascii(&machine.chip8);
+    if actual != expected {
+        panic!("The display of {} does not match {}\n\nexpected:\n{}\nactual:\n{}", case.rom, golden.display(), expected, actual);
+    }
+}
+
+#[test]
+fn arithmetic() {
+    check(Case::new("arithmetic.asm"));
+}
+
+#[test]
+fn memory() {
+    check(Case::new("memory.asm"));
+}
+
+// The community test roms are not redistributed with the crate, so their tests are ignored. Run them with
+// --ignored once the roms are copied into tests/fixtures/conformance/roms, see tests/fixtures/conformance/README.md.
+
+#[test]
+#[ignore = "needs the community test roms in tests/fixtures/conformance/roms"]
+fn ibm_logo() {
+    check(Case::new("roms/2-ibm-logo.ch8"));
+}
+
+#[test]
+#[ignore = "needs the community test roms in tests/fixtures/conformance/roms"]
+fn corax_plus() {
+    check(Case::new("roms/3-corax+.ch8"));
+}
+
+#[test]
+#[ignore = "needs the community test roms in tests/fixtures/conformance/roms"]
+fn flags() {
+    check(Case::new("roms/4-flags.ch8"));
+}
+
+#[test]
+#[ignore = "needs the community test roms in tests/fixtures/conformance/roms"]
+fn quirks_vip() {
+    // 1 at 0x1FF skips the menu and tests the CHIP-8 quirks, the display wait needs a slow machine
+    check(Case {
+        quirks: Quirks::vip(),
+        frames: 600,
+        cycles_per_frame: 15,
+        poke: &[(0x1FF, 1)],
+        ..Case::new("roms/5-quirks.ch8")
+    });
+}
+
+#[test]
+#[ignore = "needs the community test roms in tests/fixtures/conformance/roms"]
+fn keypad_fx0a() {
+    // 3 at 0x1FF skips the menu and tests FX0A, which has to wait for the key to be released
+    check(Case {
+        input: "30 5\n40 -",
+        poke: &[(0x1FF, 3)],
+        ..Case::new("roms/6-keypad.ch8")
+    });
+}
@@ -0,0 +1,299 @@
+use chip_8_emu::assembler::assemble;
+use chip_8_emu::chip8::Chip8;
+use chip_8_emu::debugger::{Command, Debugger, OpcodePattern, Register, StopReason};
+use chip_8_emu::watchpoint::{Access, WatchKind, Watchpoint};
+
+mod common;
+use common::snapshot;
+
+fn parse(command: &str) -> Command {
+    command.parse::<Command>().unwrap_or_else(|x| panic!("{}: {}", command, x))
+}
+
+fn parse_err(command: &str) -> String {
+    command.parse::<Command>().err().unwrap_or_else(|| panic!("{} parsed", command))
+}
+
+#[test]
+fn parses_commands_and_their_short_names() {
+    for (command, expected) in [
+        ("step", Command::Step(1)),
+        ("s 10", Command::Step(10)),
+        ("continue", Command::Continue),
+        ("c", Command::Continue),
+        ("step-back", Command::StepBack(1)),
+        ("sb 25", Command::StepBack(25)),
+        ("reverse-continue", Command::ReverseContinue),
+        ("rc", Command::ReverseContinue),
+        ("break 0x2A0", Command::Break(0x2A0)),
+        ("b 2a0", Command::Break(0x2A0)),
+        ("delete 2A0", Command::Delete(0x2A0)),
+        ("unwatch 0X300", Command::Unwatch(0x300)),
+        ("breakpoints", Command::Breakpoints),
+        ("registers", Command::Registers),
+        ("r", Command::Registers),
+        ("stack", Command::Stack),
+        ("mem 300", Command::Memory { address: 0x300, len: 0x40 }),
+        ("x 300 10", Command::Memory { address: 0x300, len: 0x10 }),
+        ("set va ff", Command::Set { register: Register::V(0xA), value: 0xFF }),
+        ("set I 0x300", Command::Set { register: Register::I, value: 0x300 }),
+        ("set pc 200", Command::Set { register: Register::Pc, value: 0x200 }),
+        ("set dt 3C", Command::Set { register: Register::DelayTimer, value: 0x3C }),
+        ("set st 1", Command::Set { register: Register::SoundTimer, value: 1 }),
+        ("help", Command::Help),
+        ("H", Command::Help),
+        ("  quit  ", Command::Quit),
+        ("q", Command::Quit),
+    ] {
+        assert_eq!(parse(command), expected, "{}", command);
+    }
+}
+
+#[test]
+fn parses_watchpoints() {
+    for (command, start, end, kind) in [
+        ("watch 300", 0x300, 0x300, WatchKind::ReadWrite),
+        ("w 300 30F", 0x300, 0x30F, WatchKind::ReadWrite),
+        ("watch 300 r", 0x300, 0x300, WatchKind::Read),
+        ("watch 300 30f W", 0x300, 0x30F, WatchKind::Write),
+        ("watch 0x300 0x301 rw", 0x300, 0x301, WatchKind::ReadWrite),
+    ] {
+        assert_eq!(parse(command), Command::Watch(Watchpoint { start, end, kind }), "{}", command);
+    }
+}
+
+#[test]
+fn rejects_bad_commands() {
+    for (command, error) in [
+        ("", "No command given"),
+        ("jump 200", "Unknown command: jump"),
+        ("break", "break is missing an argument"),
+        ("break 2G0", "Invalid number: 2G0"),
+        ("break 10000", "Invalid number: 10000"),
+        ("step ten", "Invalid count: ten"),
+        ("step -1", "Invalid count: -1"),
+        ("set", "set is missing an argument"),
+        ("set v0", "set is missing an argument"),
+        ("set vg 1", "Unknown register: vg"),
+        ("set v10 1", "Unknown register: v10"),
+        ("watch", "watch is missing an argument"),
+        ("watch 300 x", "Unknown watch kind: x"),
+        ("watch 300 2FF", "ends before it starts"),
+        ("breakop D1", "not 4 characters long"),
+    ] {
+        let err = parse_err(command);
+        assert!(err.contains(error), "{} for {}", err, command);
+    }
+}
+
+#[test]
+fn opcode_patterns_match_with_wildcards() {
+    let draw = "DXYN".parse::<OpcodePattern>().unwrap();
+    assert!(draw.matches(0xD125));
+    assert!(draw.matches(0xD000));
+    assert!(!draw.matches(0xC125));
+
+    let bcd = "F.33".parse::<OpcodePattern>().unwrap();
+    assert!(bcd.matches(0xF333));
+    assert!(!bcd.matches(0xF355));
+
+    let exact = "00e0".parse::<OpcodePattern>().unwrap();
+    assert!(exact.matches(0x00E0));
+    assert!(!exact.matches(0x00EE));
+}
+
+#[test]
+fn opcode_patterns_display_their_wildcards_as_dots() {
+    assert_eq!("DXYN".parse::<OpcodePattern>().unwrap().to_string(), "D...");
+    assert_eq!("f.33".parse::<OpcodePattern>().unwrap().to_string(), "F.33");
+    assert_eq!(parse("breakop 8xy4"), Command::BreakOpcode("8..4".parse().unwrap()));
+    assert_eq!(parse("deleteop 8xy4"), Command::DeleteOpcode("8..4".parse().unwrap()));
+}
+
+#[test]
+fn rejects_opcode_patterns_of_the_wrong_length() {
+    for pattern in ["", "D12", "D1234", "DXYNN"] {
+        assert!(pattern.parse::<OpcodePattern>().is_err(), "{}", pattern);
+    }
+}
+
+// Uses everything replaying depends on: random numbers, the timers, key input, memory and the display.
+const SCRIBBLE: &str = "
+loop:
+    RND V0, #FF
+    ADD V1, V0
+    LD V2, 5
+    SKNP V2
+    CALL pressed
+    LD I, digits
+    LD B, V1
+    LD F, V0
+    DRW V1, V3, 5
+    LD DT, V0
+    LD V4, DT
+    JP loop
+pressed:
+    ADD V3, 1
+    LD I, counter
+    LD [I], V3
+    RET
+counter:
+    DB 0, 0, 0, 0
+digits:
+    DB 0, 0, 0
+";
+
+// the first instruction of pressed and its FX55
+const PRESSED: u16 = 0x7301;
+const STORE_COUNTER: u16 = 0xF355;
+// the cycles key 5 is held for
+const HELD: [std::ops::Range<u64>; 2] = [300..320, 1200..1260];
+
+// A run of the rom one step at a time, with the state before every cycle.
+struct Run {
+    debugger: Debugger,
+    chip8: Chip8,
+    states: Vec<Vec<u8>>,
+    // the pc before every cycle
+    pcs: Vec<u16>,
+}
+
+fn run(cycles: u64) -> Run {
+    let mut chip8 = Chip8::default().with_seed(99);
+    chip8.load_program(&assemble(SCRIBBLE, std::path::Path::new("test.asm")).unwrap());
+    let mut run = Run { debugger: Debugger::new(10), chip8, states: Vec::new(), pcs: Vec::new() };
+    for cycle in 0..cycles {
+        run.chip8.key_state[5] = HELD.iter().any(|held| held.contains(&cycle)) as u8;
+        run.states.push(snapshot(&mut run.chip8));
+        run.pcs.push(run.chip8.pc);
+        run.debugger.execute(&mut run.chip8, Command::Step(1));
+    }
+    run.states.push(snapshot(&mut run.chip8));
+    run.pcs.push(run.chip8.pc);
+    run
+}
+
+fn opcode_at(run: &Run, cycle: usize) -> u16 {
+    run.chip8.peek_word(run.pcs[cycle]).unwrap()
+}
+
+fn cycle_of(debugger: &mut Debugger, chip8: &mut Chip8) -> String {
+    let registers = debugger.execute(chip8, Command::Registers);
+    registers.split("cycle ").nth(1).unwrap().trim().to_string()
+}
+
+#[test]
+fn step_back_restores_the_state_exactly() {
+    let mut run = run(2500);
+    assert!(run.chip8.v_registers[3] > 0, "the key was never seen");
+
+    // back across the snapshots at 2000 and 1000, and through the held key
+    for (count, cycle) in [(1, 2499), (150, 2349), (1100, 1249), (449, 800), (800, 0)] {
+        run.debugger.execute(&mut run.chip8, Command::StepBack(count));
+        assert_eq!(cycle_of(&mut run.debugger, &mut run.chip8), cycle.to_string());
+        assert!(snapshot(&mut run.chip8) == run.states[cycle], "step-back {} did not restore cycle {}", count, cycle);
+    }
+}
+
+#[test]
+fn step_back_matches_a_fresh_run() {
+    let mut long = run(2500);
+    long.debugger.execute(&mut long.chip8, Command::StepBack(1270));
+    let mut fresh = run(1230);
+    assert!(snapshot(&mut long.chip8) == snapshot(&mut fresh.chip8));
+}
+
+#[test]
+fn reverse_continue_stops_at_the_previous_breakpoint() {
+    let mut run = run(2600);
+    let pressed = (0..run.pcs.len()).find(|&cycle| opcode_at(&run, cycle) == PRESSED).map(|cycle| run.pcs[cycle]).unwrap();
+    run.debugger.execute(&mut run.chip8, Command::Break(pressed));
+
+    // the last time pressed was reached, while the key was held the second time
+    let expected = (0..run.pcs.len() - 1).rev().find(|&cycle| run.pcs[cycle] == pressed).unwrap();
+    assert!(HELD[1].contains(&(expected as u64)));
+
+    let out = run.debugger.execute(&mut run.chip8, Command::ReverseContinue);
+    assert!(out.starts_with(&format!("Breakpoint at {:#05X}", pressed)), "{}", out);
+    assert_eq!(cycle_of(&mut run.debugger, &mut run.chip8), expected.to_string());
+    assert!(snapshot(&mut run.chip8) == run.states[expected]);
+
+    // and the time before that, in the first press
+    run.debugger.execute(&mut run.chip8, Command::ReverseContinue);
+    let before = (0..expected).rev().find(|&cycle| run.pcs[cycle] == pressed).unwrap();
+    assert!(HELD[1].contains(&(before as u64)));
+    assert_eq!(cycle_of(&mut run.debugger, &mut run.chip8), before.to_string());
+    assert!(snapshot(&mut run.chip8) == run.states[before]);
+}
+
+#[test]
+fn reverse_continue_stops_after_the_previous_watchpoint_hit() {
+    let mut run = run(2600);
+    let stores: Vec<usize> = (0..run.pcs.len() - 1).filter(|&cycle| opcode_at(&run, cycle) == STORE_COUNTER).collect();
+    // the address of the counter is in the LD I, counter before the store
+    let counter = run.chip8.peek_word(run.pcs[stores[0]] - 2).unwrap() & 0xFFF;
+    run.debugger.execute(&mut run.chip8, Command::Watch(Watchpoint { start: counter, end: counter + 3, kind: WatchKind::Write }));
+
+    // watchpoints stop after the instruction, so the state is the one after the last store
+    let expected = stores.last().unwrap() + 1;
+    let out = run.debugger.execute(&mut run.chip8, Command::ReverseContinue);
+    assert!(out.contains(&format!("Write {:#05X}", counter + 3)), "{}", out);
+    assert_eq!(cycle_of(&mut run.debugger, &mut run.chip8), expected.to_string());
+    assert!(snapshot(&mut run.chip8) == run.states[expected]);
+    // the store runs a few cycles after the key check, in the second press
+    assert!((HELD[1].start..HELD[1].end + 10).contains(&(expected as u64)));
+}
+
+#[test]
+fn reverse_continue_without_a_stop_goes_to_the_start() {
+    let mut run = run(1500);
+    let out = run.debugger.execute(&mut run.chip8, Command::ReverseContinue);
+    assert!(out.starts_with("Reached the start of the history"), "{}", out);
+    assert!(snapshot(&mut run.chip8) == run.states[0]);
+}
+
+#[test]
+fn step_back_keeps_registers_that_were_set() {
+    // SCRIBBLE never touches V5
+    let mut run = run(1234);
+    run.debugger.execute(&mut run.chip8, Command::Set { register: Register::V(5), value: 0xAB });
+    let edited = snapshot(&mut run.chip8);
+    run.debugger.execute(&mut run.chip8, Command::Step(10));
+
+    run.debugger.execute(&mut run.chip8, Command::StepBack(4));
+    assert_eq!(cycle_of(&mut run.debugger, &mut run.chip8), "1240");
+    assert_eq!(run.chip8.v_registers[5], 0xAB);
+
+    run.debugger.execute(&mut run.chip8, Command::StepBack(6));
+    assert!(snapshot(&mut run.chip8) == edited);
+
+    // and before the edit it is gone again
+    run.debugger.execute(&mut run.chip8, Command::StepBack(1));
+    assert!(snapshot(&mut run.chip8) == run.states[1233]);
+    assert_eq!(run.chip8.v_registers[5], 0);
+}
+
+#[test]
+fn reverse_continue_keeps_registers_that_were_set() {
+    let mut run = run(1234);
+    run.debugger.execute(&mut run.chip8, Command::Set { register: Register::V(5), value: 0xAB });
+    run.debugger.execute(&mut run.chip8, Command::Step(20));
+    let pc = run.chip8.pc;
+    run.debugger.execute(&mut run.chip8, Command::Step(1));
+    run.debugger.execute(&mut run.chip8, Command::Break(pc));
+
+    run.debugger.execute(&mut run.chip8, Command::ReverseContinue);
+    assert_eq!(run.chip8.pc, pc);
+    assert_eq!(run.chip8.v_registers[5], 0xAB);
+}
+
+#[test]
+fn reports_watch_hits_as_stop_reasons() {
+    let mut chip8 = Chip8::default().with_i(0x300).with_opcode(0xF055);
+    chip8.watchpoints.push(Watchpoint { start: 0x300, end: 0x300, kind: WatchKind::Write });
+    let mut debugger = Debugger::new(10);
+    let Some(StopReason::Watchpoint(hits)) = debugger.cycle(&mut chip8) else {
+        panic!("no watchpoint hit");
+    };
+    assert_eq!(hits[0].access, Access::Write);
+}
@@ -0,0 +1,53 @@
+use chip_8_emu::assembler::assemble_file;
+use chip_8_emu::disasm::{Disassembly, LineKind, Syntax};
+
+mod common;
+use common::fixture;
+
+// mixed.ch8 is mixed.asm assembled, the listings next to it were checked by hand
+fn assert_renders_as(rom: &str, syntax: Syntax, listing: &str) {
+    let rom = std::fs::read(fixture("disasm", rom)).unwrap();
+    let expected = std::fs::read_to_string(fixture("disasm", listing)).unwrap();
+    assert_eq!(Disassembly::trace(&rom, 0x200, 0x200).render(syntax), expected);
+}
+
+#[test]
+fn renders_octo() {
+    assert_renders_as("mixed.ch8", Syntax::Octo, "mixed.octo.txt");
+}
+
+#[test]
+fn renders_cowgod() {
+    assert_renders_as("mixed.ch8", Syntax::Cowgod, "mixed.cowgod.txt");
+}
+
+#[test]
+fn fixture_is_the_assembled_source() {
+    assert_eq!(assemble_file(&fixture("disasm", "mixed.asm")).unwrap(), std::fs::read(fixture("disasm", "mixed.ch8")).unwrap());
+}
+
+#[test]
+fn unreached_bytes_are_data() {
+    let rom = std::fs::read(fixture("disasm", "mixed.ch8")).unwrap();
+    let disassembly = Disassembly::trace(&rom, 0x200, 0x200);
+    let data: Vec<u16> = disassembly.lines.iter().filter(|line| line.kind == LineKind::Data).map(|line| line.address).collect();
+    assert_eq!(data, [0x21C, 0x224]);
+}
+
+#[test]
+fn targets_inside_an_instruction_are_not_labelled() {
+    // the jump lands on the address word of F000 NNNN
+    let rom = std::fs::read(fixture("disasm", "mixed.ch8")).unwrap();
+    let disassembly = Disassembly::trace(&rom, 0x200, 0x200);
+    assert!(!disassembly.labels.contains(&0x20C));
+    assert!(disassembly.inner_targets.contains(&0x20C));
+    assert!(disassembly.lines.iter().all(|line| line.address != 0x20C));
+}
+
+#[test]
+fn targets_outside_the_rom_are_not_labelled() {
+    let disassembly = Disassembly::trace(&[0x1A, 0xBC], 0x200, 0x200);
+    assert!(disassembly.labels.is_empty());
+    assert!(disassembly.inner_targets.is_empty());
+    assert_eq!(disassembly.render(Syntax::Cowgod), "0200  1A BC                   JP #ABC\n");
+}
@@ -0,0 +1,15 @@
+; draws a box in the middle of the screen
+X EQU 30
+Y EQU 13
+
+start:
+    CLS
+    LD I, box
+    LD V0, X
+    LD V1, Y
+    DRW V0, V1, 5
+loop:
+    JP loop
+
+box:
+    DB #F0, #90, #90, #90, #F0
@@ -0,0 +1,14 @@
+; directives, expressions and operand forms
+DEFINE STEP 2
+
+    ld v2, COUNT - 1
+    LD I, table + STEP
+    LD V0, [I]
+    SHR V0
+    JP V0, table
+    LD I, LONG sprites
+    ADD V2, -1
+    LD B, V2
+    RET
+
+INCLUDE "include/table.inc"
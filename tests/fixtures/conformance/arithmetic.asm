@@ -0,0 +1,248 @@
+; 8XYN results and flags, run with the modern quirks
+;
+; Every test draws a tick if it passes and a cross if it fails, eight to a row
+; from the top left, in the order below. The golden image is all ticks.
+;
+; VA, VB: where the next result is drawn
+; V8: VF right after the instruction under test
+; V9: 1 if the test passed
+
+    LD VA, 0
+    LD VB, 0
+
+; 1. 8XY4 without carry
+    LD V0, #10
+    LD V1, #20
+    ADD V0, V1
+    LD V8, VF
+    LD V9, 0
+    SE V0, #30
+    JP test1_done
+    SE V8, 0
+    JP test1_done
+    LD V9, 1
+test1_done:
+    CALL result
+
+; 2. 8XY4 with carry
+    LD V0, #F0
+    LD V1, #20
+    ADD V0, V1
+    LD V8, VF
+    LD V9, 0
+    SE V0, #10
+    JP test2_done
+    SE V8, 1
+    JP test2_done
+    LD V9, 1
+test2_done:
+    CALL result
+
+; 3. 8XY4 carrying exactly #100
+    LD V0, #80
+    LD V1, #80
+    ADD V0, V1
+    LD V8, VF
+    LD V9, 0
+    SE V0, 0
+    JP test3_done
+    SE V8, 1
+    JP test3_done
+    LD V9, 1
+test3_done:
+    CALL result
+
+; 4. 8XY5 without borrow
+    LD V0, #30
+    LD V1, #10
+    SUB V0, V1
+    LD V8, VF
+    LD V9, 0
+    SE V0, #20
+    JP test4_done
+    SE V8, 1
+    JP test4_done
+    LD V9, 1
+test4_done:
+    CALL result
+
+; 5. 8XY5 with borrow
+    LD V0, #10
+    LD V1, #30
+    SUB V0, V1
+    LD V8, VF
+    LD V9, 0
+    SE V0, #E0
+    JP test5_done
+    SE V8, 0
+    JP test5_done
+    LD V9, 1
+test5_done:
+    CALL result
+
+; 6. 8XY7 without borrow
+    LD V0, #10
+    LD V1, #30
+    SUBN V0, V1
+    LD V8, VF
+    LD V9, 0
+    SE V0, #20
+    JP test6_done
+    SE V8, 1
+    JP test6_done
+    LD V9, 1
+test6_done:
+    CALL result
+
+; 7. 8XY7 with borrow
+    LD V0, #30
+    LD V1, #10
+    SUBN V0, V1
+    LD V8, VF
+    LD V9, 0
+    SE V0, #E0
+    JP test7_done
+    SE V8, 0
+    JP test7_done
+    LD V9, 1
+test7_done:
+    CALL result
+
+; 8. 8XY6 shifts out a 1
+    LD V0, #05
+    SHR V0
+    LD V8, VF
+    LD V9, 0
+    SE V0, #02
+    JP test8_done
+    SE V8, 1
+    JP test8_done
+    LD V9, 1
+test8_done:
+    CALL result
+
+; 9. 8XY6 shifts out a 0
+    LD V0, #04
+    SHR V0
+    LD V8, VF
+    LD V9, 0
+    SE V0, #02
+    JP test9_done
+    SE V8, 0
+    JP test9_done
+    LD V9, 1
+test9_done:
+    CALL result
+
+; 10. 8XYE shifts out a 1
+    LD V0, #81
+    SHL V0
+    LD V8, VF
+    LD V9, 0
+    SE V0, #02
+    JP test10_done
+    SE V8, 1
+    JP test10_done
+    LD V9, 1
+test10_done:
+    CALL result
+
+; 11. 8XYE shifts out a 0
+    LD V0, #41
+    SHL V0
+    LD V8, VF
+    LD V9, 0
+    SE V0, #82
+    JP test11_done
+    SE V8, 0
+    JP test11_done
+    LD V9, 1
+test11_done:
+    CALL result
+
+; 12. 8XY1
+    LD V0, #0F
+    LD V1, #F0
+    OR V0, V1
+    LD V9, 0
+    SE V0, #FF
+    JP test12_done
+    LD V9, 1
+test12_done:
+    CALL result
+
+; 13. 8XY2
+    LD V0, #3C
+    LD V1, #0F
+    AND V0, V1
+    LD V9, 0
+    SE V0, #0C
+    JP test13_done
+    LD V9, 1
+test13_done:
+    CALL result
+
+; 14. 8XY3
+    LD V0, #FF
+    LD V1, #0F
+    XOR V0, V1
+    LD V9, 0
+    SE V0, #F0
+    JP test14_done
+    LD V9, 1
+test14_done:
+    CALL result
+
+; 15. 8XY0
+    LD V0, 0
+    LD V1, #5A
+    LD V0, V1
+    LD V9, 0
+    SE V0, #5A
+    JP test15_done
+    LD V9, 1
+test15_done:
+    CALL result
+
+; 16. 7XNN wraps without touching VF
+    LD VF, 5
+    LD V0, #FF
+    ADD V0, 2
+    LD V8, VF
+    LD V9, 0
+    SE V0, 1
+    JP test16_done
+    SE V8, 5
+    JP test16_done
+    LD V9, 1
+test16_done:
+    CALL result
+
+done:
+    JP done
+
+; draws the result of a test and moves on to the next position
+result:
+    LD I, cross
+    SE V9, 0
+    LD I, tick
+    DRW VA, VB, 5
+    ADD VA, 8
+    SE VA, 64
+    RET
+    LD VA, 0
+    ADD VB, 6
+    RET
+
+tick:
+    DB %00000001
+    DB %00000010
+    DB %10000100
+    DB %01001000
+    DB %00110000
+cross:
+    DB %10001000
+    DB %01010000
+    DB %00100000
+    DB %01010000
+    DB %10001000
@@ -0,0 +1,191 @@
+; Memory, index, subroutine and skip instructions, run with the modern quirks
+;
+; Every test draws a tick if it passes and a cross if it fails, eight to a row
+; from the top left, in the order below. The golden image is all ticks.
+;
+; VA, VB: where the next result is drawn
+; V8: VF right after the instruction under test
+; V9: 1 if the test passed
+
+    LD VA, 0
+    LD VB, 0
+
+; 1. FX55 and FX65 include VX
+    LD V0, 1
+    LD V1, 2
+    LD V2, 3
+    LD V3, 4
+    LD I, buffer
+    LD [I], V3
+    LD V0, 0
+    LD V1, 0
+    LD V2, 0
+    LD V3, 0
+    LD V4, #FF
+    LD I, buffer
+    LD V4, [I]
+    LD V9, 0
+    SE V0, 1
+    JP test1_done
+    SE V3, 4
+    JP test1_done
+    SE V4, 0
+    JP test1_done
+    LD V9, 1
+test1_done:
+    CALL result
+
+; 2. FX55 with X = 0
+    LD V0, #77
+    LD I, single
+    LD [I], V0
+    LD V0, 0
+    LD I, single
+    LD V1, [I]
+    LD V9, 0
+    SE V0, #77
+    JP test2_done
+    SE V1, 0
+    JP test2_done
+    LD V9, 1
+test2_done:
+    CALL result
+
+; 3. FX33
+    LD V0, 254
+    LD I, buffer
+    LD B, V0
+    LD V2, [I]
+    LD V9, 0
+    SE V0, 2
+    JP test3_done
+    SE V1, 5
+    JP test3_done
+    SE V2, 4
+    JP test3_done
+    LD V9, 1
+test3_done:
+    CALL result
+
+; 4. FX1E
+    LD I, table
+    LD V0, 3
+    ADD I, V0
+    LD V0, [I]
+    LD V9, 0
+    SE V0, #33
+    JP test4_done
+    LD V9, 1
+test4_done:
+    CALL result
+
+; 5. FX29
+    LD V0, #A
+    LD F, V0
+    LD V1, [I]
+    LD V9, 0
+    SE V0, #F0
+    JP test5_done
+    SE V1, #90
+    JP test5_done
+    LD V9, 1
+test5_done:
+    CALL result
+
+; 6. 2NNN and 00EE
+    LD V0, 0
+    CALL set_v0
+    LD V1, 1
+    LD V9, 0
+    SE V0, #42
+    JP test6_done
+    SE V1, 1
+    JP test6_done
+    LD V9, 1
+test6_done:
+    CALL result
+
+; 7. BNNN
+    LD V0, 2
+    JP V0, jumps
+bnnn_back:
+    LD V9, 0
+    SE V1, #B0
+    JP test7_done
+    LD V9, 1
+test7_done:
+    CALL result
+
+; 8. 3XNN, 4XNN, 5XY0 and 9XY0
+    LD V1, 0
+    LD V0, 7
+    LD V2, 7
+    LD V3, 8
+    SE V0, 7
+    ADD V1, #10
+    SNE V0, 8
+    ADD V1, #10
+    SE V0, V2
+    ADD V1, #10
+    SNE V0, V3
+    ADD V1, #10
+    SE V0, 8
+    ADD V1, 1
+    SNE V0, 7
+    ADD V1, 1
+    SE V0, V3
+    ADD V1, 1
+    SNE V0, V2
+    ADD V1, 1
+    LD V9, 0
+    SE V1, 4
+    JP test8_done
+    LD V9, 1
+test8_done:
+    CALL result
+
+done:
+    JP done
+
+; draws the result of a test and moves on to the next position
+result:
+    LD I, cross
+    SE V9, 0
+    LD I, tick
+    DRW VA, VB, 5
+    ADD VA, 8
+    SE VA, 64
+    RET
+    LD VA, 0
+    ADD VB, 6
+    RET
+
+tick:
+    DB %00000001
+    DB %00000010
+    DB %10000100
+    DB %01001000
+    DB %00110000
+cross:
+    DB %10001000
+    DB %01010000
+    DB %00100000
+    DB %01010000
+    DB %10001000
+
+set_v0:
+    LD V0, #42
+    RET
+
+; BNNN lands on the second jump
+jumps:
+    JP done
+    LD V1, #B0
+    JP bnnn_back
+
+buffer:
+    DB 0, 0, 0, 0, 0, 0, 0, 0
+single:
+    DB 0, 0
+table:
+    DB #00, #11, #22, #33, #44
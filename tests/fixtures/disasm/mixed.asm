@@ -0,0 +1,21 @@
+; code and data mixed together, for the disassembler golden tests
+start:
+    CLS
+    LD I, sprite
+    LD V0, 10
+    CALL draw
+    SE V0, 10
+index:
+    LD I, LONG #0300
+    SKP V1
+    JP index + 2
+    SKNP V2
+    JP #0ABC
+    JP start
+draw:
+    DRW V0, V0, 5
+    RET
+sprite:
+    DB #F0, #90, #90, #90, #F0
+unreached:
+    DB #01, #02, #03, #04, #05, #06, #07, #08, #09
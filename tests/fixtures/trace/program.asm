@@ -0,0 +1,17 @@
+; a short program for the trace golden test, the trace is program.txt
+    LD V0, 5
+    LD V1, #2A
+    LD DT, V0
+    CALL sub
+    LD I, sprite
+    DRW V0, V1, 3
+    SE VF, 0
+    EXIT
+end:
+    JP end
+sub:
+    ADD V0, V1
+    LD ST, V1
+    RET
+sprite:
+    DB #80, #40, #20
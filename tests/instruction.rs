@@ -0,0 +1,78 @@
+use chip_8_emu::instruction::{decode, Instruction};
+
+#[test]
+fn encode_is_the_inverse_of_decode() {
+    for opcode in 0..=0xFFFF {
+        if let Some(instruction) = decode(opcode) {
+            assert_eq!(instruction.encode(), opcode, "{:04X} decoded to {:?}", opcode, instruction);
+        }
+    }
+}
+
+#[test]
+fn decodes_every_opcode_family() {
+    // one opcode of every instruction, with all the operand nibbles in use where there are any
+    for (opcode, instruction) in [
+        (0x0123, Instruction::Sys { nnn: 0x123 }),
+        (0x00E0, Instruction::ClearScreen),
+        (0x00EE, Instruction::Return),
+        (0x00C7, Instruction::ScrollDown { n: 7 }),
+        (0x00D7, Instruction::ScrollUp { n: 7 }),
+        (0x00FB, Instruction::ScrollRight),
+        (0x00FC, Instruction::ScrollLeft),
+        (0x00FD, Instruction::Exit),
+        (0x00FE, Instruction::LowRes),
+        (0x00FF, Instruction::HighRes),
+        (0x1ABC, Instruction::Jump { nnn: 0xABC }),
+        (0x2ABC, Instruction::Call { nnn: 0xABC }),
+        (0x3A42, Instruction::SkipEqImm { x: 0xA, nn: 0x42 }),
+        (0x4A42, Instruction::SkipNeImm { x: 0xA, nn: 0x42 }),
+        (0x5AB0, Instruction::SkipEqReg { x: 0xA, y: 0xB }),
+        (0x5AB2, Instruction::SaveRange { x: 0xA, y: 0xB }),
+        (0x5AB3, Instruction::LoadRange { x: 0xA, y: 0xB }),
+        (0x6A42, Instruction::LoadImm { x: 0xA, nn: 0x42 }),
+        (0x7A42, Instruction::AddImm { x: 0xA, nn: 0x42 }),
+        (0x8AB0, Instruction::Move { x: 0xA, y: 0xB }),
+        (0x8AB1, Instruction::Or { x: 0xA, y: 0xB }),
+        (0x8AB2, Instruction::And { x: 0xA, y: 0xB }),
+        (0x8AB3, Instruction::Xor { x: 0xA, y: 0xB }),
+        (0x8AB4, Instruction::Add { x: 0xA, y: 0xB }),
+        (0x8AB5, Instruction::Sub { x: 0xA, y: 0xB }),
+        (0x8AB6, Instruction::ShiftRight { x: 0xA, y: 0xB }),
+        (0x8AB7, Instruction::SubReverse { x: 0xA, y: 0xB }),
+        (0x8ABE, Instruction::ShiftLeft { x: 0xA, y: 0xB }),
+        (0x9AB0, Instruction::SkipNeReg { x: 0xA, y: 0xB }),
+        (0xAABC, Instruction::LoadIndex { nnn: 0xABC }),
+        (0xBABC, Instruction::JumpOffset { x: 0xA, nnn: 0xABC }),
+        (0xCA42, Instruction::Random { x: 0xA, nn: 0x42 }),
+        (0xDAB7, Instruction::Draw { x: 0xA, y: 0xB, n: 7 }),
+        (0xEA9E, Instruction::SkipKeyPressed { x: 0xA }),
+        (0xEAA1, Instruction::SkipKeyNotPressed { x: 0xA }),
+        (0xF000, Instruction::LoadLongIndex),
+        (0xF201, Instruction::SelectPlanes { n: 2 }),
+        (0xF002, Instruction::LoadAudioPattern),
+        (0xFA07, Instruction::LoadDelay { x: 0xA }),
+        (0xFA0A, Instruction::WaitKey { x: 0xA }),
+        (0xFA15, Instruction::SetDelay { x: 0xA }),
+        (0xFA18, Instruction::SetSound { x: 0xA }),
+        (0xFA1E, Instruction::AddIndex { x: 0xA }),
+        (0xFA29, Instruction::LoadFont { x: 0xA }),
+        (0xFA30, Instruction::LoadBigFont { x: 0xA }),
+        (0xFA33, Instruction::StoreBcd { x: 0xA }),
+        (0xFA3A, Instruction::SetPitch { x: 0xA }),
+        (0xFA55, Instruction::StoreRegisters { x: 0xA }),
+        (0xFA65, Instruction::LoadRegisters { x: 0xA }),
+        (0xFA75, Instruction::StoreFlags { x: 0xA }),
+        (0xFA85, Instruction::LoadFlags { x: 0xA }),
+    ] {
+        assert_eq!(decode(opcode), Some(instruction), "{:04X}", opcode);
+        assert_eq!(instruction.encode(), opcode, "{:?}", instruction);
+    }
+}
+
+#[test]
+fn rejects_unknown_opcodes() {
+    for opcode in [0x5AB1, 0x8AB8, 0x9AB1, 0xEA00, 0xF100, 0xF102, 0xFAFF] {
+        assert_eq!(decode(opcode), None, "{:04X}", opcode);
+    }
+}
@@ -0,0 +1,80 @@
+use chip_8_emu::chip8::{Chip8, MachineMode};
+use chip_8_emu::quirks::Quirks;
+
+mod common;
+use common::snapshot;
+
+fn machine(mode: MachineMode) -> Chip8 {
+    let mut chip8 = Chip8::new(mode, Quirks::vip())
+        .with_seed(7)
+        .with_v(0xF, 1)
+        .with_i(0x2A0)
+        .with_pc(0x246)
+        .with_stack(&[0x204, 0x222])
+        .with_key(0xB, true)
+        .with_timers(30, 4)
+        .with_memory(0x200, &[0x00, 0xE0, 0xA2, 0x2A, 0x60, 0x0C]);
+    chip8.waiting_key = Some(0xB);
+    chip8.rpl_flags[3] = 9;
+    chip8.display[5] = 3;
+    chip8
+}
+
+#[test]
+fn round_trips_exactly() {
+    for mode in [MachineMode::Chip8, MachineMode::Xochip] {
+        let mut chip8 = machine(mode);
+        let mut loaded = Chip8::from_json(&chip8.to_json()).unwrap();
+        assert_eq!(snapshot(&mut loaded), snapshot(&mut chip8), "{:?}", mode);
+    }
+}
+
+#[test]
+fn round_trips_the_hires_display() {
+    let mut chip8 = machine(MachineMode::Chip8);
+    chip8.set_hires(true);
+    chip8.display[200] = 1;
+    let mut loaded = Chip8::from_json(&chip8.to_json()).unwrap();
+    assert_eq!(snapshot(&mut loaded), snapshot(&mut chip8));
+}
+
+#[test]
+fn is_readable() {
+    let json: serde_json::Value = serde_json::from_str(&machine(MachineMode::Chip8).to_json()).unwrap();
+    assert_eq!(json["memory"][0x20], "0200: 00 E0 A2 2A 60 0C 00 00 00 00 00 00 00 00 00 00");
+    assert_eq!(json["memory"].as_array().unwrap().len(), 0x1000 / 16);
+    assert_eq!(json["display"][0], format!(".....@{}", ".".repeat(58)));
+    assert_eq!(json["display"].as_array().unwrap().len(), 32);
+    assert_eq!(json["quirks"]["load_store"], "increment");
+    assert_eq!(json["pc"], 0x246);
+}
+
+#[test]
+fn loads_edits() {
+    let json = machine(MachineMode::Chip8).to_json()
+        .replace("0200: 00 E0", "0200: 12 00")
+        .replace(&format!(".....@{}", ".".repeat(58)), &format!("#{}", ".".repeat(63)));
+    let chip8 = Chip8::from_json(&json).unwrap();
+    assert_eq!(chip8.memory[0x200..0x202], [0x12, 0x00]);
+    assert_eq!(chip8.display[..6], [1, 0, 0, 0, 0, 0]);
+}
+
+#[test]
+fn rejects_bad_edits() {
+    let json = machine(MachineMode::Chip8).to_json();
+    for (from, to, error) in [
+        ("0200: 00 E0", "0200: 00 G0", "bad byte G0 at 0200"),
+        ("\"0200: ", "\"0300: ", "memory line 0300 should be at 0200"),
+        ("\"0FF0: 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00\"", "\"0FF0: 00\"", "memory is 0xFF1 bytes long"),
+        (".....@", ".....x", "display row 0 has 'x'"),
+        (".....@", "......@", "display row 1 is not as long as the first row"),
+        ("\"stack_pointer\": 2", "\"stack_pointer\": 17", "stack_pointer is 17"),
+        ("\"waiting_key\": 11", "\"waiting_key\": 16", "waiting_key is 16"),
+        ("\"planes\": 1", "\"planes\": 4", "planes is 4"),
+        ("\"key_state\": [\n    0,", "\"key_state\": [\n    2,", "key_state of key 0 is 2"),
+    ] {
+        assert!(json.contains(from), "{}", from);
+        let err = Chip8::from_json(&json.replacen(from, to, 1)).err().unwrap().to_string();
+        assert!(err.contains(error), "{} for {}", err, to);
+    }
+}
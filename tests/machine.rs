@@ -0,0 +1,161 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chip_8_emu::assembler::assemble;
+use chip_8_emu::audio::{AudioSink, Silence, Sound};
+use chip_8_emu::chip8::Chip8;
+use chip_8_emu::machine::{Machine, VirtualClock, FRAME_DURATION, MAX_CATCH_UP};
+use chip_8_emu::options::Options;
+use chip_8_emu::quirks::Quirks;
+
+// counts in V0 forever, one instruction per count
+const COUNTER: &str = "
+loop:
+    ADD V0, 1
+    JP loop
+";
+
+fn machine(source: &str, hz: u64, clock: &VirtualClock) -> Machine {
+    let mut chip8 = Chip8::default();
+    chip8.load_program(&assemble(source, std::path::Path::new("test.asm")).unwrap());
+    Machine::new(chip8, hz, Box::new(clock.clone()), Box::new(Silence))
+}
+
+// records every sound the machine sends
+struct Recorder(Arc<Mutex<Vec<Option<Sound>>>>);
+
+impl AudioSink for Recorder {
+    fn set_sound(&mut self, sound: Option<Sound>) {
+        self.0.lock().unwrap().push(sound);
+    }
+}
+
+#[test]
+fn runs_cycles_per_frame_each_frame() {
+    let options = Options { cycles_per_frame: Some(10), ..Options::default() };
+    let mut machine = machine(COUNTER, options.cycles_per_second(), &VirtualClock::new());
+    for frame in 1..=5 {
+        machine.run_frame().unwrap();
+        assert_eq!(machine.cycles(), frame * 10);
+    }
+}
+
+#[test]
+fn carries_fractions_of_cycles_over() {
+    // 300 and 333 Hz used to run at the same speed
+    let clock = VirtualClock::new();
+    let mut slow = machine(COUNTER, 300, &clock);
+    let mut fast = machine(COUNTER, 333, &clock);
+    for _ in 0..60 {
+        slow.run_frame().unwrap();
+        fast.run_frame().unwrap();
+    }
+    assert_eq!(slow.cycles(), 300);
+    assert_eq!(fast.cycles(), 333);
+}
+
+#[test]
+fn runs_above_1000_hz() {
+    let mut machine = machine(COUNTER, 3000, &VirtualClock::new());
+    machine.run_frame().unwrap();
+    assert_eq!(machine.cycles(), 50);
+}
+
+#[test]
+fn ticks_timers_once_per_frame() {
+    let source = "
+    LD V1, 3
+    LD DT, V1
+    LD ST, V1
+loop:
+    JP loop
+    ";
+    let mut machine = machine(source, 600, &VirtualClock::new());
+    for expected in [2, 1, 0, 0] {
+        machine.run_frame().unwrap();
+        assert_eq!(machine.chip8.delay_timer, expected);
+        assert_eq!(machine.chip8.sound_timer, expected);
+    }
+}
+
+#[test]
+fn runs_the_frames_that_are_due() {
+    let clock = VirtualClock::new();
+    let mut machine = machine(COUNTER, 600, &clock);
+
+    clock.advance(FRAME_DURATION / 2);
+    machine.run_due().unwrap();
+    assert_eq!(machine.frames(), 0);
+    assert_eq!(machine.until_next_frame(), FRAME_DURATION - FRAME_DURATION / 2);
+
+    clock.advance(FRAME_DURATION / 2);
+    machine.run_due().unwrap();
+    assert_eq!(machine.frames(), 1);
+
+    clock.advance(FRAME_DURATION * 3);
+    machine.run_due().unwrap();
+    assert_eq!(machine.frames(), 4);
+    assert_eq!(machine.cycles(), 40);
+}
+
+#[test]
+fn does_not_catch_up_on_long_stalls() {
+    let clock = VirtualClock::new();
+    let mut machine = machine(COUNTER, 600, &clock);
+    clock.advance(Duration::from_secs(10));
+    machine.run_due().unwrap();
+    assert_eq!(machine.frames(), (MAX_CATCH_UP.as_nanos() / FRAME_DURATION.as_nanos()) as u64);
+}
+
+#[test]
+fn sync_forgets_paused_time() {
+    let clock = VirtualClock::new();
+    let mut machine = machine(COUNTER, 600, &clock);
+    clock.advance(FRAME_DURATION * 5);
+    machine.sync();
+    machine.run_due().unwrap();
+    assert_eq!(machine.frames(), 0);
+    assert_eq!(machine.until_next_frame(), FRAME_DURATION);
+}
+
+#[test]
+fn sounds_while_the_sound_timer_is_active() {
+    let source = "
+    LD V1, 2
+    LD ST, V1
+loop:
+    JP loop
+    ";
+    let sounds = Arc::new(Mutex::new(Vec::new()));
+    let mut chip8 = Chip8::default();
+    chip8.load_program(&assemble(source, std::path::Path::new("test.asm")).unwrap());
+    let mut machine = Machine::new(chip8, 600, Box::new(VirtualClock::new()), Box::new(Recorder(sounds.clone())));
+    for _ in 0..4 {
+        machine.run_frame().unwrap();
+    }
+    assert_eq!(*sounds.lock().unwrap(), [Some(Sound::Buzzer), Some(Sound::Buzzer), None, None]);
+}
+
+#[test]
+fn display_wait_ends_the_frame_after_a_draw() {
+    let source = "
+loop:
+    ADD V0, 1
+    DRW V1, V1, 1
+    JP loop
+    ";
+    let clock = VirtualClock::new();
+    let mut waiting = machine(source, 600, &clock);
+    waiting.chip8.quirks = Quirks::vip();
+    let mut free = machine(source, 600, &clock);
+    free.chip8.quirks = Quirks { display_wait: false, ..Quirks::vip() };
+    for _ in 0..3 {
+        waiting.run_frame().unwrap();
+        free.run_frame().unwrap();
+    }
+    // one draw per frame, and the timers still tick every frame
+    assert_eq!(waiting.chip8.v_registers[0], 3);
+    assert_eq!(waiting.cycles(), 8);
+    assert_eq!(waiting.frames(), 3);
+    assert_eq!(free.cycles(), 30);
+}
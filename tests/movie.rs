@@ -0,0 +1,97 @@
+use chip_8_emu::assembler::assemble;
+use chip_8_emu::audio::Silence;
+use chip_8_emu::chip8::MachineMode;
+use chip_8_emu::machine::{Machine, VirtualClock};
+use chip_8_emu::movie::Movie;
+use chip_8_emu::quirks::Quirks;
+
+mod common;
+use common::snapshot;
+
+// draws digits at random places, counting up in V3 while key 5 is held
+const SCRIBBLE: &str = "
+loop:
+    RND V0, #3F
+    RND V1, #1F
+    LD V2, 5
+    SKNP V2
+    ADD V3, 1
+    LD F, V3
+    DRW V0, V1, 5
+    JP loop
+";
+
+fn rom() -> Vec<u8> {
+    assemble(SCRIBBLE, std::path::Path::new("test.asm")).unwrap()
+}
+
+// runs the rom for 60 frames, pressing key 5 from frame 10 to 25 and key A from frame 40 on
+fn record(rom: &[u8]) -> (Movie, Vec<u8>) {
+    let movie = Movie::new(rom, 1234, MachineMode::Chip8, Quirks::modern(), 600);
+    let mut machine = Machine::new(movie.machine(rom).unwrap(), movie.hz, Box::new(VirtualClock::new()), Box::new(Silence));
+    machine.record_movie(movie);
+    for frame in 0..60 {
+        match frame {
+            10 => machine.chip8.key_state[5] = 1,
+            25 => machine.chip8.key_state[5] = 0,
+            40 => machine.chip8.key_state[0xA] = 1,
+            _ => {},
+        }
+        machine.run_frame().unwrap();
+    }
+    assert!(machine.chip8.v_registers[3] > 0);
+    let state = snapshot(&mut machine.chip8);
+    (machine.take_movie().unwrap(), state)
+}
+
+#[test]
+fn records_key_changes() {
+    let (movie, _) = record(&rom());
+    assert_eq!(movie.frames, 60);
+    assert_eq!(movie.input.to_string(), "0 -\n10 5\n25 -\n40 A\n");
+}
+
+#[test]
+fn plays_back_exactly() {
+    let rom = rom();
+    let (movie, recorded) = record(&rom);
+    let movie = movie.to_string().parse::<Movie>().unwrap();
+
+    let mut machine = Machine::new(movie.machine(&rom).unwrap(), movie.hz, Box::new(VirtualClock::new()), Box::new(Silence));
+    machine.play_movie(movie);
+    while machine.is_playing_movie() {
+        machine.run_frame().unwrap();
+    }
+    assert_eq!(machine.frames(), 60);
+    assert_eq!(snapshot(&mut machine.chip8), recorded);
+}
+
+#[test]
+fn round_trips_as_text() {
+    let (movie, _) = record(&rom());
+    let text = movie.to_string();
+    assert!(text.starts_with("chip-8-emu movie 1\n"), "{}", text);
+    assert_eq!(text.parse::<Movie>().unwrap(), movie);
+}
+
+#[test]
+fn rejects_other_roms() {
+    let (movie, _) = record(&rom());
+    let err = movie.machine(&[0x12, 0x00]).err().unwrap();
+    assert!(err.contains("different rom"), "{}", err);
+}
+
+#[test]
+fn rejects_bad_movies() {
+    let text = record(&rom()).0.to_string();
+    for (from, to, error) in [
+        ("chip-8-emu movie 1", "chip-8-emu movie 2", "newer than this emulator supports"),
+        ("chip-8-emu movie", "chip-8-emu film", "Not a movie"),
+        ("seed 1234\n", "", "no seed"),
+        ("hz 600", "hz fast", "Invalid hz: fast"),
+        ("10 5", "10 5 X", "input"),
+    ] {
+        let err = text.replacen(from, to, 1).parse::<Movie>().err().unwrap();
+        assert!(err.contains(error), "{} for {}", err, to);
+    }
+}
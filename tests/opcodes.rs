@@ -0,0 +1,683 @@
+use chip_8_emu::audio::Sound;
+use chip_8_emu::chip8::{Chip8, MachineMode, StepOutcome};
+use chip_8_emu::errors::Chip8Error;
+use chip_8_emu::quirks::{LoadStoreQuirk, Quirks};
+use chip_8_emu::rng::Xorshift;
+
+// Every test pokes one opcode at 0x200 with the builders, runs a single cycle and checks the state after it.
+
+fn run(chip8: Chip8) -> Chip8 {
+    let mut chip8 = chip8;
+    chip8.single_cycle().unwrap();
+    chip8
+}
+
+// 00E0 - CLS
+
+#[test]
+fn clear_screen() {
+    let mut chip8 = Chip8::default().with_opcode(0x00E0);
+    chip8.display.fill(1);
+    let chip8 = run(chip8);
+    assert!(chip8.display.iter().all(|&pixel| pixel == 0));
+    assert_eq!(chip8.pc, 0x202);
+}
+
+// 00EE - RET
+
+#[test]
+fn return_pops_the_stack() {
+    let chip8 = run(Chip8::default().with_stack(&[0x300, 0x400]).with_opcode(0x00EE));
+    assert_eq!(chip8.pc, 0x400);
+    assert_eq!(chip8.stack_pointer, 1);
+}
+
+#[test]
+fn return_with_an_empty_stack_underflows() {
+    let mut chip8 = Chip8::default().with_opcode(0x00EE);
+    assert!(matches!(chip8.single_cycle(), Err(Chip8Error::StackUnderflow { pc: 0x200, opcode: 0x00EE })));
+}
+
+// 0NNN - SYS
+
+#[test]
+fn sys_is_ignored() {
+    let chip8 = run(Chip8::default().with_opcode(0x0123));
+    assert_eq!(chip8.pc, 0x202);
+    assert_eq!(chip8.stack_pointer, 0);
+}
+
+// 1NNN - JP
+
+#[test]
+fn jump() {
+    let chip8 = run(Chip8::default().with_opcode(0x1ABC));
+    assert_eq!(chip8.pc, 0xABC);
+}
+
+#[test]
+fn fetch_past_the_end_of_memory() {
+    let mut chip8 = Chip8::default().with_opcode(0x00E0);
+    chip8.single_cycle().unwrap();
+    let mut chip8 = chip8.with_pc(0xFFF);
+    assert!(matches!(chip8.single_cycle(), Err(Chip8Error::MemoryOutOfRange { pc: 0xFFF, opcode: 0, address: 0xFFF })));
+}
+
+// 2NNN - CALL
+
+#[test]
+fn call_pushes_the_return_address() {
+    let chip8 = run(Chip8::default().with_opcode(0x2ABC));
+    assert_eq!(chip8.pc, 0xABC);
+    assert_eq!(chip8.stack_pointer, 1);
+    assert_eq!(chip8.jump_stack[0], 0x202);
+}
+
+#[test]
+fn call_with_a_full_stack_overflows() {
+    let mut chip8 = Chip8::default().with_stack(&[0x300; 16]).with_opcode(0x2ABC);
+    assert!(matches!(chip8.single_cycle(), Err(Chip8Error::StackOverflow { pc: 0x200, opcode: 0x2ABC })));
+}
+
+// 3XNN, 4XNN, 5XY0 and 9XY0 - SE and SNE
+
+#[test]
+fn skip_if_equal_immediate() {
+    assert_eq!(run(Chip8::default().with_v(0x3, 0x42).with_opcode(0x3342)).pc, 0x204);
+    assert_eq!(run(Chip8::default().with_v(0x3, 0x41).with_opcode(0x3342)).pc, 0x202);
+}
+
+#[test]
+fn skip_if_not_equal_immediate() {
+    assert_eq!(run(Chip8::default().with_v(0x3, 0x41).with_opcode(0x4342)).pc, 0x204);
+    assert_eq!(run(Chip8::default().with_v(0x3, 0x42).with_opcode(0x4342)).pc, 0x202);
+}
+
+#[test]
+fn skip_if_registers_equal() {
+    assert_eq!(run(Chip8::default().with_v(0x1, 7).with_v(0x2, 7).with_opcode(0x5120)).pc, 0x204);
+    assert_eq!(run(Chip8::default().with_v(0x1, 7).with_v(0x2, 8).with_opcode(0x5120)).pc, 0x202);
+}
+
+#[test]
+fn skip_if_registers_not_equal() {
+    assert_eq!(run(Chip8::default().with_v(0x1, 7).with_v(0x2, 8).with_opcode(0x9120)).pc, 0x204);
+    assert_eq!(run(Chip8::default().with_v(0x1, 7).with_v(0x2, 7).with_opcode(0x9120)).pc, 0x202);
+}
+
+#[test]
+fn skip_at_the_end_of_memory_does_not_read_past_it() {
+    // only XO-CHIP looks at the skipped instruction
+    let chip8 = run(Chip8::default().with_pc(0xFFE).with_opcode(0x3000));
+    assert_eq!(chip8.pc, 0x1002);
+}
+
+// 6XNN and 7XNN - LD and ADD with a byte
+
+#[test]
+fn load_immediate() {
+    let chip8 = run(Chip8::default().with_opcode(0x6A42));
+    assert_eq!(chip8.v_registers[0xA], 0x42);
+    assert_eq!(chip8.pc, 0x202);
+}
+
+#[test]
+fn add_immediate_wraps_without_touching_vf() {
+    let chip8 = run(Chip8::default().with_v(0x1, 0xFF).with_v(0xF, 0x55).with_opcode(0x7102));
+    assert_eq!(chip8.v_registers[0x1], 0x01);
+    assert_eq!(chip8.v_registers[0xF], 0x55);
+}
+
+// 8XY0 to 8XY3 - LD, OR, AND and XOR
+
+#[test]
+fn move_register() {
+    let chip8 = run(Chip8::default().with_v(0x2, 0x42).with_opcode(0x8120));
+    assert_eq!(chip8.v_registers[0x1], 0x42);
+    assert_eq!(chip8.v_registers[0x2], 0x42);
+}
+
+#[test]
+fn bitwise_operations() {
+    for (opcode, expected) in [(0x8121, 0b1110), (0x8122, 0b1000), (0x8123, 0b0110)] {
+        let chip8 = run(Chip8::default().with_v(0x1, 0b1100).with_v(0x2, 0b1010).with_v(0xF, 0x55).with_opcode(opcode));
+        assert_eq!(chip8.v_registers[0x1], expected, "{:04X}", opcode);
+        assert_eq!(chip8.v_registers[0xF], 0x55, "{:04X}", opcode);
+    }
+}
+
+#[test]
+fn bitwise_operations_reset_vf_with_the_vf_reset_quirk() {
+    for opcode in [0x8121, 0x8122, 0x8123] {
+        let chip8 = run(Chip8::default().with_quirks(Quirks::vip()).with_v(0xF, 0x55).with_opcode(opcode));
+        assert_eq!(chip8.v_registers[0xF], 0, "{:04X}", opcode);
+    }
+}
+
+// 8XY4 - ADD with carry
+
+#[test]
+fn add_sets_carry() {
+    let chip8 = run(Chip8::default().with_v(0x1, 0x20).with_v(0x2, 0x22).with_opcode(0x8124));
+    assert_eq!(chip8.v_registers[0x1], 0x42);
+    assert_eq!(chip8.v_registers[0xF], 0);
+}
+
+#[test]
+fn add_carries_at_exactly_0x100() {
+    let chip8 = run(Chip8::default().with_v(0x1, 0xFF).with_v(0x2, 0x01).with_opcode(0x8124));
+    assert_eq!(chip8.v_registers[0x1], 0x00);
+    assert_eq!(chip8.v_registers[0xF], 1);
+
+    let chip8 = run(Chip8::default().with_v(0x1, 0xFE).with_v(0x2, 0x01).with_opcode(0x8124));
+    assert_eq!(chip8.v_registers[0x1], 0xFF);
+    assert_eq!(chip8.v_registers[0xF], 0);
+}
+
+// 8XY5 and 8XY7 - SUB and SUBN with borrow
+
+#[test]
+fn sub_sets_no_borrow() {
+    let chip8 = run(Chip8::default().with_v(0x1, 0x42).with_v(0x2, 0x02).with_opcode(0x8125));
+    assert_eq!(chip8.v_registers[0x1], 0x40);
+    assert_eq!(chip8.v_registers[0xF], 1);
+
+    let chip8 = run(Chip8::default().with_v(0x1, 0x02).with_v(0x2, 0x03).with_opcode(0x8125));
+    assert_eq!(chip8.v_registers[0x1], 0xFF);
+    assert_eq!(chip8.v_registers[0xF], 0);
+}
+
+#[test]
+fn sub_does_not_borrow_when_equal() {
+    let chip8 = run(Chip8::default().with_v(0x1, 0x42).with_v(0x2, 0x42).with_opcode(0x8125));
+    assert_eq!(chip8.v_registers[0x1], 0);
+    assert_eq!(chip8.v_registers[0xF], 1);
+}
+
+#[test]
+fn sub_reverse_sets_no_borrow() {
+    let chip8 = run(Chip8::default().with_v(0x1, 0x02).with_v(0x2, 0x42).with_opcode(0x8127));
+    assert_eq!(chip8.v_registers[0x1], 0x40);
+    assert_eq!(chip8.v_registers[0xF], 1);
+
+    let chip8 = run(Chip8::default().with_v(0x1, 0x03).with_v(0x2, 0x02).with_opcode(0x8127));
+    assert_eq!(chip8.v_registers[0x1], 0xFF);
+    assert_eq!(chip8.v_registers[0xF], 0);
+}
+
+#[test]
+fn sub_reverse_does_not_borrow_when_equal() {
+    let chip8 = run(Chip8::default().with_v(0x1, 0x42).with_v(0x2, 0x42).with_opcode(0x8127));
+    assert_eq!(chip8.v_registers[0x1], 0);
+    assert_eq!(chip8.v_registers[0xF], 1);
+}
+
+// 8XY6 and 8XYE - SHR and SHL
+
+#[test]
+fn shifts_vy_into_vx() {
+    let quirks = Quirks { shifting: false, ..Quirks::default() };
+    let chip8 = run(Chip8::default().with_quirks(quirks).with_v(0x1, 0xFF).with_v(0x2, 0b0000_0011).with_opcode(0x8126));
+    assert_eq!(chip8.v_registers[0x1], 0b0000_0001);
+    assert_eq!(chip8.v_registers[0xF], 1);
+
+    let chip8 = run(Chip8::default().with_quirks(quirks).with_v(0x1, 0xFF).with_v(0x2, 0b0100_0000).with_opcode(0x812E));
+    assert_eq!(chip8.v_registers[0x1], 0b1000_0000);
+    assert_eq!(chip8.v_registers[0xF], 0);
+}
+
+#[test]
+fn shifts_vx_in_place_with_the_shifting_quirk() {
+    let quirks = Quirks { shifting: true, ..Quirks::default() };
+    let chip8 = run(Chip8::default().with_quirks(quirks).with_v(0x1, 0b0000_0010).with_v(0x2, 0xFF).with_opcode(0x8126));
+    assert_eq!(chip8.v_registers[0x1], 0b0000_0001);
+    assert_eq!(chip8.v_registers[0xF], 0);
+
+    let chip8 = run(Chip8::default().with_quirks(quirks).with_v(0x1, 0b1000_0001).with_v(0x2, 0x00).with_opcode(0x812E));
+    assert_eq!(chip8.v_registers[0x1], 0b0000_0010);
+    assert_eq!(chip8.v_registers[0xF], 1);
+}
+
+// VF as an operand: the flag is written last, and computed from the operands as they were
+
+#[test]
+fn vf_as_vx_holds_the_flag() {
+    // 8FY4, 8FY5, 8FY6, 8FY7 and 8FYE
+    for (opcode, vf, vy, expected) in [
+        (0x8F14, 0xFF, 0x01, 1),
+        (0x8F15, 0x01, 0x02, 0),
+        (0x8F16, 0xFF, 0x02, 0),
+        (0x8F17, 0x02, 0x01, 0),
+        (0x8F1E, 0xFF, 0x80, 1),
+    ] {
+        let chip8 = run(Chip8::default().with_quirks(Quirks { shifting: false, ..Quirks::default() }).with_v(0xF, vf).with_v(0x1, vy).with_opcode(opcode));
+        assert_eq!(chip8.v_registers[0xF], expected, "{:04X}", opcode);
+    }
+}
+
+#[test]
+fn vf_as_vy_is_read_before_the_flag_is_written() {
+    // V1 + VF = 0x01 + 0xFF carries, the sum is computed with VF before it becomes the carry
+    let chip8 = run(Chip8::default().with_v(0x1, 0x01).with_v(0xF, 0xFF).with_opcode(0x81F4));
+    assert_eq!(chip8.v_registers[0x1], 0x00);
+    assert_eq!(chip8.v_registers[0xF], 1);
+
+    // V1 - VF = 0x05 - 0x03
+    let chip8 = run(Chip8::default().with_v(0x1, 0x05).with_v(0xF, 0x03).with_opcode(0x81F5));
+    assert_eq!(chip8.v_registers[0x1], 0x02);
+    assert_eq!(chip8.v_registers[0xF], 1);
+
+    // VF - V1 = 0x03 - 0x05
+    let chip8 = run(Chip8::default().with_v(0x1, 0x05).with_v(0xF, 0x03).with_opcode(0x81F7));
+    assert_eq!(chip8.v_registers[0x1], 0xFE);
+    assert_eq!(chip8.v_registers[0xF], 0);
+
+    // V1 = VF >> 1, without the shifting quirk
+    let quirks = Quirks { shifting: false, ..Quirks::default() };
+    let chip8 = run(Chip8::default().with_quirks(quirks).with_v(0xF, 0b0000_0101).with_opcode(0x81F6));
+    assert_eq!(chip8.v_registers[0x1], 0b0000_0010);
+    assert_eq!(chip8.v_registers[0xF], 1);
+}
+
+// ANNN and BNNN - LD I and JP V0
+
+#[test]
+fn load_index() {
+    let chip8 = run(Chip8::default().with_opcode(0xA123));
+    assert_eq!(chip8.i, 0x123);
+}
+
+#[test]
+fn jump_with_offset() {
+    let quirks = Quirks { jumping: false, ..Quirks::default() };
+    let chip8 = run(Chip8::default().with_quirks(quirks).with_v(0x0, 0x10).with_v(0x3, 0x20).with_opcode(0xB300));
+    assert_eq!(chip8.pc, 0x310);
+}
+
+#[test]
+fn jump_with_offset_uses_vx_with_the_jumping_quirk() {
+    let quirks = Quirks { jumping: true, ..Quirks::default() };
+    let chip8 = run(Chip8::default().with_quirks(quirks).with_v(0x0, 0x10).with_v(0x3, 0x20).with_opcode(0xB300));
+    assert_eq!(chip8.pc, 0x320);
+}
+
+// CXNN - RND
+
+#[test]
+fn random_is_masked_and_seeded() {
+    let mut rng = Xorshift::new(42);
+    let expected = rng.next_u8() & 0x0F;
+    let chip8 = run(Chip8::default().with_seed(42).with_opcode(0xC10F));
+    assert_eq!(chip8.v_registers[0x1], expected);
+}
+
+// DXYN - DRW
+
+#[test]
+fn draw_xors_and_sets_collision() {
+    let chip8 = Chip8::default()
+        .with_memory(0x300, &[0b1100_0000])
+        .with_i(0x300)
+        .with_v(0x1, 2)
+        .with_v(0x2, 3)
+        .with_opcode(0xD121)
+        .with_memory(0x202, &[0xD1, 0x21]);
+    let mut chip8 = run(chip8);
+    assert_eq!(chip8.display[3 * 64 + 2], 1);
+    assert_eq!(chip8.display[3 * 64 + 3], 1);
+    assert_eq!(chip8.display[3 * 64 + 4], 0);
+    assert_eq!(chip8.v_registers[0xF], 0);
+
+    // drawing it again erases it
+    chip8.single_cycle().unwrap();
+    assert!(chip8.display.iter().all(|&pixel| pixel == 0));
+    assert_eq!(chip8.v_registers[0xF], 1);
+}
+
+#[test]
+fn draw_returns_drew_with_the_display_wait_quirk() {
+    let mut chip8 = Chip8::default().with_quirks(Quirks::vip()).with_opcode(0xD001);
+    assert_eq!(chip8.single_cycle().unwrap(), StepOutcome::Drew);
+}
+
+// EX9E and EXA1 - SKP and SKNP
+
+#[test]
+fn skip_if_key_pressed() {
+    assert_eq!(run(Chip8::default().with_v(0x1, 0xA).with_key(0xA, true).with_opcode(0xE19E)).pc, 0x204);
+    assert_eq!(run(Chip8::default().with_v(0x1, 0xA).with_opcode(0xE19E)).pc, 0x202);
+}
+
+#[test]
+fn skip_if_key_not_pressed() {
+    assert_eq!(run(Chip8::default().with_v(0x1, 0xA).with_opcode(0xE1A1)).pc, 0x204);
+    assert_eq!(run(Chip8::default().with_v(0x1, 0xA).with_key(0xA, true).with_opcode(0xE1A1)).pc, 0x202);
+}
+
+// FX07, FX15 and FX18 - timers
+
+#[test]
+fn load_delay_timer() {
+    let chip8 = run(Chip8::default().with_timers(0x42, 0).with_opcode(0xF107));
+    assert_eq!(chip8.v_registers[0x1], 0x42);
+}
+
+#[test]
+fn set_timers() {
+    let chip8 = run(Chip8::default().with_v(0x1, 0x42).with_opcode(0xF115));
+    assert_eq!(chip8.delay_timer, 0x42);
+    let chip8 = run(Chip8::default().with_v(0x1, 0x42).with_opcode(0xF118));
+    assert_eq!(chip8.sound_timer, 0x42);
+}
+
+// FX0A - LD K, see tests/wait_key.rs for the release behaviour
+
+#[test]
+fn wait_key_repeats_until_a_key_is_released() {
+    let chip8 = run(Chip8::default().with_opcode(0xF10A));
+    assert_eq!(chip8.pc, 0x200);
+
+    let mut chip8 = run(Chip8::default().with_key(0x7, true).with_opcode(0xF10A));
+    assert_eq!(chip8.pc, 0x200);
+    chip8.key_state[0x7] = 0;
+    chip8.single_cycle().unwrap();
+    assert_eq!(chip8.pc, 0x202);
+    assert_eq!(chip8.v_registers[0x1], 0x7);
+}
+
+// FX1E - ADD I
+
+#[test]
+fn add_to_index() {
+    let chip8 = run(Chip8::default().with_i(0x300).with_v(0x1, 0x42).with_v(0xF, 0x55).with_opcode(0xF11E));
+    assert_eq!(chip8.i, 0x342);
+    assert_eq!(chip8.v_registers[0xF], 0x55);
+}
+
+// FX29 - LD F
+
+#[test]
+fn load_font() {
+    let chip8 = run(Chip8::default().with_v(0x1, 0xA).with_opcode(0xF129));
+    assert_eq!(chip8.i, 0xA * 5);
+    assert_eq!(chip8.memory[chip8.i as usize..][..5], [0xF0, 0x90, 0xF0, 0x90, 0x90]);
+}
+
+// FX33 - LD B
+
+#[test]
+fn store_bcd() {
+    let chip8 = run(Chip8::default().with_i(0x300).with_v(0x1, 254).with_opcode(0xF133));
+    assert_eq!(chip8.memory[0x300..0x303], [2, 5, 4]);
+    assert_eq!(chip8.i, 0x300);
+}
+
+#[test]
+fn store_bcd_out_of_memory() {
+    let mut chip8 = Chip8::default().with_i(0xFFE).with_opcode(0xF133);
+    assert!(matches!(chip8.single_cycle(), Err(Chip8Error::MemoryOutOfRange { address: 0x1000, .. })));
+}
+
+// FX55 and FX65 - LD [I] and LD VX, [I]
+
+#[test]
+fn store_registers_up_to_vx() {
+    let chip8 = Chip8::default().with_i(0x300).with_v(0x0, 1).with_v(0x1, 2).with_v(0x2, 3).with_v(0x3, 4);
+    let chip8 = run(chip8.with_quirks(Quirks { load_store: LoadStoreQuirk::Unchanged, ..Quirks::default() }).with_opcode(0xF255));
+    assert_eq!(chip8.memory[0x300..0x304], [1, 2, 3, 0]);
+    assert_eq!(chip8.i, 0x300);
+}
+
+#[test]
+fn load_registers_up_to_vx() {
+    let chip8 = Chip8::default().with_i(0x300).with_memory(0x300, &[1, 2, 3, 4]);
+    let chip8 = run(chip8.with_quirks(Quirks { load_store: LoadStoreQuirk::Unchanged, ..Quirks::default() }).with_opcode(0xF265));
+    assert_eq!(chip8.v_registers[..4], [1, 2, 3, 0]);
+    assert_eq!(chip8.i, 0x300);
+}
+
+#[test]
+fn load_store_quirk_moves_the_index() {
+    for (load_store, expected) in [
+        (LoadStoreQuirk::Unchanged, 0x300),
+        (LoadStoreQuirk::Increment, 0x303),
+        (LoadStoreQuirk::IncrementByX, 0x302),
+    ] {
+        let quirks = Quirks { load_store, ..Quirks::default() };
+        assert_eq!(run(Chip8::default().with_quirks(quirks).with_i(0x300).with_opcode(0xF255)).i, expected, "{:?}", load_store);
+        assert_eq!(run(Chip8::default().with_quirks(quirks).with_i(0x300).with_opcode(0xF265)).i, expected, "{:?}", load_store);
+    }
+}
+
+#[test]
+fn store_registers_out_of_memory() {
+    let mut chip8 = Chip8::default().with_i(0xFFE).with_opcode(0xF255);
+    assert!(matches!(chip8.single_cycle(), Err(Chip8Error::MemoryOutOfRange { address: 0x1000, .. })));
+}
+
+// SUPER-CHIP
+
+// pixel index of x, y on the current display
+fn pixel(chip8: &Chip8, x: usize, y: usize) -> u8 {
+    chip8.display[y * chip8.display_width() + x]
+}
+
+fn with_pixel(mut chip8: Chip8, x: usize, y: usize) -> Chip8 {
+    let width = chip8.display_width();
+    chip8.display[y * width + x] = 1;
+    chip8
+}
+
+// 00CN - SCD
+
+#[test]
+fn scroll_down() {
+    let chip8 = run(with_pixel(Chip8::default(), 3, 0).with_opcode(0x00C2));
+    assert_eq!(pixel(&chip8, 3, 2), 1);
+    assert_eq!(pixel(&chip8, 3, 0), 0);
+    assert_eq!(chip8.display.iter().filter(|&&pixel| pixel != 0).count(), 1);
+}
+
+#[test]
+fn scroll_down_drops_the_bottom_rows() {
+    let chip8 = run(with_pixel(Chip8::default(), 3, 31).with_opcode(0x00C1));
+    assert!(chip8.display.iter().all(|&pixel| pixel == 0));
+}
+
+// 00FB - SCR
+
+#[test]
+fn scroll_right() {
+    let chip8 = run(with_pixel(Chip8::default(), 0, 5).with_opcode(0x00FB));
+    assert_eq!(pixel(&chip8, 4, 5), 1);
+    assert_eq!(pixel(&chip8, 0, 5), 0);
+}
+
+// 00FC - SCL
+
+#[test]
+fn scroll_left() {
+    let chip8 = run(with_pixel(Chip8::default(), 6, 5).with_opcode(0x00FC));
+    assert_eq!(pixel(&chip8, 2, 5), 1);
+    assert_eq!(pixel(&chip8, 6, 5), 0);
+}
+
+// 00FD - EXIT
+
+#[test]
+fn exit_halts() {
+    let mut chip8 = Chip8::default().with_opcode(0x00FD);
+    assert_eq!(chip8.single_cycle().unwrap(), StepOutcome::Halted);
+    assert!(chip8.halted);
+}
+
+// 00FE and 00FF - LOW and HIGH
+
+#[test]
+fn high_and_low_resolution_resize_and_clear_the_display() {
+    let chip8 = run(with_pixel(Chip8::default(), 0, 0).with_opcode(0x00FF));
+    assert!(chip8.hires);
+    assert_eq!(chip8.display.len(), 128 * 64);
+    assert!(chip8.display.iter().all(|&pixel| pixel == 0));
+
+    let chip8 = run(with_pixel(chip8, 100, 60).with_opcode(0x00FE));
+    assert!(!chip8.hires);
+    assert_eq!(chip8.display.len(), 64 * 32);
+    assert!(chip8.display.iter().all(|&pixel| pixel == 0));
+}
+
+// DXY0 - DRW 16x16
+
+#[test]
+fn draw_16x16_sprite() {
+    let mut chip8 = Chip8::default().with_v(0x1, 100).with_v(0x2, 40).with_i(0x300).with_memory(0x300, &[0xFF; 32]).with_opcode(0xD120);
+    chip8.set_hires(true);
+    let chip8 = run(chip8);
+    assert_eq!(chip8.display.iter().filter(|&&pixel| pixel != 0).count(), 16 * 16);
+    assert_eq!(pixel(&chip8, 100, 40), 1);
+    assert_eq!(pixel(&chip8, 115, 55), 1);
+    assert_eq!(pixel(&chip8, 116, 40), 0);
+    assert_eq!(chip8.v_registers[0xF], 0);
+
+    let chip8 = run(chip8.with_pc(0x200));
+    assert!(chip8.display.iter().all(|&pixel| pixel == 0));
+    assert_eq!(chip8.v_registers[0xF], 1);
+}
+
+// FX30 - LD HF, VX
+
+#[test]
+fn load_big_font() {
+    let chip8 = run(Chip8::default().with_v(0x1, 7).with_opcode(0xF130));
+    assert_eq!(chip8.i, 0x050 + 7 * 10);
+    assert_eq!(chip8.memory[chip8.i as usize..chip8.i as usize + 10], [0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18]);
+}
+
+// FX75 and FX85 - LD R, VX and LD VX, R
+
+#[test]
+fn store_and_load_flags_up_to_vx() {
+    let chip8 = run(Chip8::default().with_v(0x0, 1).with_v(0x1, 2).with_v(0x2, 3).with_v(0x3, 4).with_opcode(0xF275));
+    assert_eq!(chip8.rpl_flags[..4], [1, 2, 3, 0]);
+
+    let mut chip8 = chip8.with_pc(0x200).with_opcode(0xF185);
+    chip8.v_registers = [0; 16];
+    let chip8 = run(chip8);
+    assert_eq!(chip8.v_registers[..3], [1, 2, 0]);
+}
+
+// XO-CHIP
+
+fn xochip() -> Chip8 {
+    Chip8::new(MachineMode::Xochip, Quirks::xochip())
+}
+
+#[test]
+fn xo_chip_opcodes_are_unknown_in_chip8_mode() {
+    for opcode in [0x5012, 0x5013, 0xF000, 0xF101, 0xF002, 0xF03A] {
+        let mut chip8 = Chip8::default().with_opcode(opcode);
+        assert!(matches!(chip8.single_cycle(), Err(Chip8Error::UnknownOpcode { pc: 0x200, .. })), "{:04X}", opcode);
+    }
+}
+
+// 00DN - SCU
+
+#[test]
+fn scroll_up_is_ignored_in_chip8_mode() {
+    let chip8 = run(with_pixel(Chip8::default(), 3, 5).with_opcode(0x00D2));
+    assert_eq!(chip8.pc, 0x202);
+    assert_eq!(pixel(&chip8, 3, 5), 1);
+}
+
+#[test]
+fn scroll_up() {
+    let chip8 = run(with_pixel(xochip(), 3, 5).with_opcode(0x00D2));
+    assert_eq!(pixel(&chip8, 3, 3), 1);
+    assert_eq!(pixel(&chip8, 3, 5), 0);
+}
+
+// 5XY2 - SAVE VX - VY
+
+#[test]
+fn save_register_range() {
+    let chip8 = xochip().with_v(0x1, 1).with_v(0x2, 2).with_v(0x3, 3).with_i(0x300);
+    let chip8 = run(chip8.with_opcode(0x5132));
+    assert_eq!(chip8.memory[0x300..0x304], [1, 2, 3, 0]);
+    assert_eq!(chip8.i, 0x300);
+
+    let chip8 = run(chip8.with_pc(0x200).with_opcode(0x5312));
+    assert_eq!(chip8.memory[0x300..0x304], [3, 2, 1, 0]);
+}
+
+// 5XY3 - LOAD VX - VY
+
+#[test]
+fn load_register_range() {
+    let chip8 = run(xochip().with_i(0x300).with_memory(0x300, &[1, 2, 3, 4]).with_opcode(0x5243));
+    assert_eq!(chip8.v_registers[..6], [0, 0, 1, 2, 3, 0]);
+    assert_eq!(chip8.i, 0x300);
+
+    let chip8 = run(xochip().with_i(0x300).with_memory(0x300, &[1, 2, 3, 4]).with_opcode(0x5423));
+    assert_eq!(chip8.v_registers[..6], [0, 0, 3, 2, 1, 0]);
+}
+
+// F000 NNNN - LD I, NNNN
+
+#[test]
+fn load_long_index() {
+    let chip8 = run(xochip().with_memory(0x200, &[0xF0, 0x00, 0xAB, 0xCD]));
+    assert_eq!(chip8.i, 0xABCD);
+    assert_eq!(chip8.pc, 0x204);
+}
+
+#[test]
+fn skips_over_long_index_loads() {
+    // SE V0, #42 followed by F000 NNNN skips all 4 bytes
+    let chip8 = run(xochip().with_v(0x0, 0x42).with_memory(0x200, &[0x30, 0x42, 0xF0, 0x00, 0xAB, 0xCD]));
+    assert_eq!(chip8.pc, 0x206);
+
+    let chip8 = run(xochip().with_memory(0x200, &[0x30, 0x42, 0xF0, 0x00, 0xAB, 0xCD]));
+    assert_eq!(chip8.pc, 0x202);
+}
+
+// FN01 - PLANE N
+
+#[test]
+fn select_planes() {
+    let chip8 = run(xochip().with_opcode(0xF201));
+    assert_eq!(chip8.planes, 2);
+}
+
+#[test]
+fn planes_are_drawn_and_cleared_separately() {
+    // PLANE 3, DRW V0, V0, 1 with a sprite per plane, PLANE 1, CLS
+    let program = [0xF3, 0x01, 0xD0, 0x01, 0xF1, 0x01, 0x00, 0xE0];
+    let mut chip8 = xochip().with_i(0x300).with_memory(0x300, &[0x80, 0xC0]).with_memory(0x200, &program);
+    chip8.single_cycle().unwrap();
+    chip8.single_cycle().unwrap();
+    assert_eq!(pixel(&chip8, 0, 0), 0b11);
+    assert_eq!(pixel(&chip8, 1, 0), 0b10);
+
+    chip8.single_cycle().unwrap();
+    chip8.single_cycle().unwrap();
+    assert_eq!(pixel(&chip8, 0, 0), 0b10);
+    assert_eq!(pixel(&chip8, 1, 0), 0b10);
+}
+
+// F002 - AUDIO
+
+#[test]
+fn load_audio_pattern() {
+    let pattern: Vec<u8> = (0..16).collect();
+    let chip8 = run(xochip().with_i(0x300).with_memory(0x300, &pattern).with_opcode(0xF002));
+    assert_eq!(chip8.audio_pattern.unwrap()[..], pattern[..]);
+    assert!(matches!(chip8.sound(), Sound::Pattern { pitch: 64, .. }));
+}
+
+// FX3A - PITCH VX
+
+#[test]
+fn set_pitch() {
+    let chip8 = run(xochip().with_v(0x4, 100).with_opcode(0xF43A));
+    assert_eq!(chip8.pitch, 100);
+}
@@ -0,0 +1,112 @@
+use chip_8_emu::chip8::Chip8;
+use chip_8_emu::rewind::{decode, encode, RewindBuffer};
+
+mod common;
+use common::snapshot;
+
+fn assert_round_trips(delta: &[u8]) {
+    assert_eq!(decode(&encode(delta), delta.len()), delta, "{:?}", delta);
+}
+
+#[test]
+fn codec_round_trips() {
+    assert_round_trips(&[]);
+    assert_round_trips(&[0; 1000]);
+    assert_round_trips(&[7; 1000]);
+    assert_round_trips(&[1, 0, 0, 2, 3, 0, 4]);
+    // runs longer than a single LEB128 byte holds
+    let long: Vec<u8> = [vec![0; 300], vec![9; 200], vec![0; 128], vec![1; 127]].concat();
+    assert_round_trips(&long);
+    // trailing zeros
+    assert_round_trips(&[5, 6, 0, 0, 0, 0]);
+}
+
+#[test]
+fn codec_stores_runs_of_zeros_as_lengths() {
+    assert!(encode(&[]).is_empty());
+    // 300 zeros and no literals, 300 is 0xAC 0x02 in LEB128
+    assert_eq!(encode(&[0; 300]), [0xAC, 0x02, 0x00]);
+    assert_eq!(encode(&[0, 0, 7, 8, 0]), [2, 2, 7, 8, 1, 0]);
+}
+
+#[test]
+fn decode_pads_to_the_length() {
+    assert_eq!(decode(&encode(&[3]), 4), [3, 0, 0, 0]);
+    assert_eq!(decode(&[], 3), [0, 0, 0]);
+}
+
+// a machine with something different in every snapshot
+fn step(chip8: &mut Chip8, n: u8) {
+    chip8.v_registers[(n % 16) as usize] = n;
+    chip8.memory[0x300 + n as usize] = n;
+    let len = chip8.display.len();
+    chip8.display[(n as usize * 37) % len] ^= 1;
+}
+
+#[test]
+fn rewinds_across_resolution_changes() {
+    let mut chip8 = Chip8::default().with_seed(3);
+    let mut buffer = RewindBuffer::new(usize::MAX);
+    let mut expected = Vec::new();
+    for n in 0..12 {
+        match n {
+            4 => chip8.set_hires(true),
+            8 => chip8.set_hires(false),
+            _ => {},
+        }
+        step(&mut chip8, n);
+        expected.push(snapshot(&mut chip8));
+        buffer.push(&mut chip8).unwrap();
+    }
+    assert_eq!(buffer.len(), 12);
+
+    // the machine moves on before rewinding
+    chip8.set_hires(true);
+    step(&mut chip8, 99);
+    while let Some(state) = expected.pop() {
+        assert!(buffer.rewind(&mut chip8).unwrap());
+        assert_eq!(snapshot(&mut chip8), state, "{} snapshots left", expected.len());
+    }
+    assert!(!buffer.rewind(&mut chip8).unwrap());
+    assert!(buffer.is_empty());
+    assert_eq!(buffer.size(), 0);
+}
+
+// the bytes a buffer holding these snapshots should use, the newest in full and the rest as deltas
+fn stored_size(snapshots: &[Vec<u8>]) -> usize {
+    let deltas: usize = snapshots.windows(2).map(|pair| {
+        let xor: Vec<u8> = (0..pair[0].len().max(pair[1].len()))
+            .map(|index| pair[0].get(index).unwrap_or(&0) ^ pair[1].get(index).unwrap_or(&0))
+            .collect();
+        encode(&xor).len()
+    }).sum();
+    snapshots.last().map_or(0, |latest| latest.len()) + deltas
+}
+
+#[test]
+fn evicts_the_oldest_snapshots_over_budget() {
+    let mut chip8 = Chip8::default().with_seed(3);
+    let budget = snapshot(&mut chip8).len() + 200;
+    let mut buffer = RewindBuffer::new(budget);
+    let mut snapshots = Vec::new();
+    for n in 0..40 {
+        if n == 20 {
+            chip8.set_hires(true);
+        }
+        step(&mut chip8, n);
+        snapshots.push(snapshot(&mut chip8));
+        buffer.push(&mut chip8).unwrap();
+
+        let kept = &snapshots[snapshots.len() - buffer.len()..];
+        assert_eq!(buffer.size(), stored_size(kept), "after {} pushes", n + 1);
+        assert!(buffer.size() <= budget || buffer.len() == 1, "after {} pushes", n + 1);
+    }
+    assert!(buffer.len() < 40);
+
+    // rewinding keeps the accounting right as well
+    let kept = snapshots.len() - buffer.len();
+    while buffer.rewind(&mut chip8).unwrap() {
+        snapshots.pop();
+        assert_eq!(buffer.size(), stored_size(&snapshots[kept..]));
+    }
+}
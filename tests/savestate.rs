@@ -0,0 +1,147 @@
+// savefile-derive expands its impls inside an anonymous const
+#![allow(non_local_definitions)]
+
+use chip_8_emu::chip8::{Chip8, HIRES_WIDTH};
+use chip_8_emu::errors::SaveStateError;
+use chip_8_emu::options::Options;
+use chip_8_emu::savestate::{rom_hash, SaveState, FORMAT_VERSION, MAGIC};
+
+const ROM: &[u8] = &[0x60, 0x42, 0x12, 0x02];
+
+fn saved(chip8: &mut Chip8) -> Vec<u8> {
+    let options = Options { hz: 840, ..Options::default() };
+    let mut bytes = Vec::new();
+    SaveState::capture(chip8, Some(rom_hash(ROM)), Some(&options)).unwrap().write(&mut bytes).unwrap();
+    bytes
+}
+
+#[test]
+fn round_trips_the_machine_and_metadata() {
+    let mut chip8 = Chip8::default().with_v(0x3, 0x42).with_i(0x345).with_pc(0x210);
+    chip8.load_program(ROM);
+    chip8.display[0] = 1;
+    let bytes = saved(&mut chip8);
+    assert!(bytes.starts_with(MAGIC));
+
+    let state = SaveState::read(&mut bytes.as_slice()).unwrap();
+    assert_eq!(state.rom_hash, Some(rom_hash(ROM)));
+    assert!(state.timestamp > 0);
+    assert_eq!(state.options().unwrap().hz, 840);
+    assert_eq!((state.thumbnail.width, state.thumbnail.height), (64, 32));
+    assert_eq!(state.thumbnail.pixels[..2], [1, 0]);
+
+    let mut restored = Chip8::default();
+    state.check_rom(rom_hash(ROM)).unwrap();
+    state.restore(&mut restored).unwrap();
+    assert_eq!(restored.v_registers[0x3], 0x42);
+    assert_eq!(restored.i, 0x345);
+    assert_eq!(restored.pc, 0x210);
+    assert_eq!(restored.memory, chip8.memory);
+}
+
+#[test]
+fn rejects_states_of_other_roms() {
+    let bytes = saved(&mut Chip8::default());
+    let state = SaveState::read(&mut bytes.as_slice()).unwrap();
+    let other = rom_hash(&[0x00, 0xE0]);
+    assert!(matches!(state.check_rom(other), Err(SaveStateError::WrongRom { rom, .. }) if rom == other));
+}
+
+#[test]
+fn scales_hires_thumbnails_down() {
+    let mut chip8 = Chip8::default();
+    chip8.set_hires(true);
+    chip8.display[HIRES_WIDTH + 3] = 1;
+    let bytes = saved(&mut chip8);
+    let state = SaveState::read(&mut bytes.as_slice()).unwrap();
+    assert_eq!(state.thumbnail.pixels.len(), 64 * 32);
+    assert_eq!(state.thumbnail.pixels[1], 1);
+}
+
+#[test]
+fn rejects_newer_format_versions() {
+    let mut bytes = saved(&mut Chip8::default());
+    bytes[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+    assert!(matches!(SaveState::read(&mut bytes.as_slice()), Err(SaveStateError::UnsupportedVersion { .. })));
+}
+
+#[test]
+fn rejects_files_that_are_not_states() {
+    assert!(matches!(SaveState::read(&mut &b"not a state"[..]), Err(SaveStateError::NotAState(_))));
+    assert!(matches!(SaveState::read(&mut &MAGIC[..]), Err(SaveStateError::Truncated)));
+}
+
+// the layout of the raw dumps written before the container, see savestate.rs
+mod v0 {
+    use savefile_derive::Savefile;
+
+    #[derive(Savefile)]
+    pub struct Chip8 {
+        pub opcode: u16,
+        pub memory: [u8; 4096],
+        pub v_registers: [u8; 16],
+        pub i: u16,
+        pub pc: u16,
+        pub delay_timer: u8,
+        pub sound_timer: u8,
+        pub jump_stack: [u16; 16],
+        pub stack_pointer: u16,
+        pub key_state: [u8; 16],
+        pub display: [u8; 2048],
+    }
+}
+
+#[test]
+fn migrates_raw_dumps() {
+    let mut old = v0::Chip8 {
+        opcode: 0x1202,
+        memory: [0; 4096],
+        v_registers: [0; 16],
+        i: 0x300,
+        pc: 0x202,
+        delay_timer: 5,
+        sound_timer: 0,
+        jump_stack: [0; 16],
+        stack_pointer: 2,
+        key_state: [0; 16],
+        display: [0; 2048],
+    };
+    old.memory[0x200..0x204].copy_from_slice(ROM);
+    old.v_registers[0] = 0x42;
+    // two calls deep, the old stack left level 0 unused
+    old.jump_stack[1] = 0x250;
+    old.jump_stack[2] = 0x262;
+    old.display[65] = 1;
+
+    // the binary saved with version 0, the library with version 1
+    for version in [0, 1] {
+        let mut bytes = Vec::new();
+        savefile::save(&mut bytes, version, &old).unwrap();
+        let state = SaveState::read(&mut bytes.as_slice()).unwrap();
+        assert_eq!(state.rom_hash, None);
+        assert!(state.options().is_none());
+        // states that do not know their rom load with any rom
+        state.check_rom(rom_hash(ROM)).unwrap();
+
+        let mut chip8 = Chip8::default();
+        state.restore(&mut chip8).unwrap();
+        assert_eq!(chip8.pc, 0x202);
+        assert_eq!(chip8.i, 0x300);
+        assert_eq!(chip8.v_registers[0], 0x42);
+        assert_eq!(chip8.delay_timer, 5);
+        assert_eq!(chip8.stack_pointer, 2);
+        assert_eq!(chip8.jump_stack[..2], [0x250, 0x262]);
+        assert_eq!(chip8.memory[0x200..0x204], *ROM);
+        assert_eq!(chip8.display[65], 1);
+        assert_eq!(state.thumbnail.pixels[65], 1);
+
+        // returning goes back through both calls
+        chip8.memory[0x202..0x204].copy_from_slice(&[0x00, 0xEE]);
+        chip8.memory[0x262..0x264].copy_from_slice(&[0x00, 0xEE]);
+        chip8.single_cycle().unwrap();
+        assert_eq!(chip8.pc, 0x262);
+        chip8.single_cycle().unwrap();
+        assert_eq!(chip8.pc, 0x250);
+        assert_eq!(chip8.stack_pointer, 0);
+    }
+}
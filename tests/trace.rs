@@ -0,0 +1,80 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use chip_8_emu::assembler::assemble_file;
+use chip_8_emu::chip8::Chip8;
+use chip_8_emu::trace::{TraceFormat, Tracer, BINARY_MAGIC, BINARY_RECORD_SIZE, BINARY_VERSION};
+
+mod common;
+use common::fixture;
+
+// a writer the test can still read after the tracer took it
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Buffer {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+// traces program.asm until it loops on itself, program.txt is that trace checked by hand
+fn trace(format: TraceFormat) -> Vec<u8> {
+    let mut chip8 = Chip8::default();
+    chip8.load_program(&assemble_file(&fixture("trace", "program.asm")).unwrap());
+    let buffer = Buffer::default();
+    let mut tracer = Tracer::new(Box::new(buffer.clone()), format).unwrap();
+    for _ in 0..12 {
+        tracer.record(&chip8).unwrap();
+        chip8.single_cycle().unwrap();
+    }
+    tracer.flush().unwrap();
+    let bytes = buffer.0.lock().unwrap().clone();
+    bytes
+}
+
+#[test]
+fn text_trace_matches_the_documented_format() {
+    let expected = std::fs::read_to_string(fixture("trace", "program.txt")).unwrap();
+    assert_eq!(String::from_utf8(trace(TraceFormat::Text)).unwrap(), expected);
+}
+
+#[test]
+fn binary_records_hold_the_fields_of_the_text_trace() {
+    let binary = trace(TraceFormat::Binary);
+    assert_eq!(binary[..4], BINARY_MAGIC);
+    assert_eq!(binary[4..8], [BINARY_VERSION, 0, 0, 0]);
+
+    let records = binary[8..].chunks(BINARY_RECORD_SIZE).collect::<Vec<_>>();
+    let text = String::from_utf8(trace(TraceFormat::Text)).unwrap();
+    assert_eq!(records.len(), text.lines().count());
+    assert!(records.iter().all(|record| record.len() == BINARY_RECORD_SIZE));
+
+    for (cycle, (record, line)) in records.iter().zip(text.lines()).enumerate() {
+        let word = |offset: usize| u16::from_be_bytes([record[offset], record[offset + 1]]);
+        let v = record[4..20].iter().map(|value| format!("{:02X}", value)).collect::<Vec<_>>().join(" ");
+        let decoded = format!(
+            "{:010} {:04X} {:04X} V={} I={:04X} SP={:02X} DT={:02X} ST={:02X} ",
+            cycle, word(0), word(2), v, word(20), record[22], record[23], record[24]
+        );
+        assert!(line.starts_with(&decoded), "record {} is {}, the text trace has {}", cycle, decoded, line);
+    }
+}
+
+#[test]
+fn full_stack_keeps_the_columns_in_place() {
+    let chip8 = Chip8::default().with_stack(&[0x202; 16]).with_opcode(0x00E0);
+    let buffer = Buffer::default();
+    let mut tracer = Tracer::new(Box::new(buffer.clone()), TraceFormat::Text).unwrap();
+    tracer.record(&chip8).unwrap();
+    tracer.flush().unwrap();
+    let line = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    let expected = std::fs::read_to_string(fixture("trace", "program.txt")).unwrap();
+    assert_eq!(line.find(" SP="), expected.find(" SP="), "{}", line);
+    assert!(line.contains(" SP=10 DT=00 "), "{}", line);
+}
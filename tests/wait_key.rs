@@ -0,0 +1,113 @@
+use chip_8_emu::assembler::assemble;
+use chip_8_emu::chip8::{Chip8, MachineMode};
+use chip_8_emu::quirks::Quirks;
+
+// waits for a key into V0 and counts the keys read in V1
+const COUNT_KEYS: &str = "
+loop:
+    LD V0, K
+    ADD V1, 1
+    JP loop
+";
+
+fn chip8(quirks: Quirks) -> Chip8 {
+    let mut chip8 = Chip8::new(MachineMode::default(), quirks);
+    chip8.load_program(&assemble(COUNT_KEYS, std::path::Path::new("test.asm")).unwrap());
+    chip8
+}
+
+fn run(chip8: &mut Chip8, cycles: usize) {
+    for _ in 0..cycles {
+        chip8.single_cycle().unwrap();
+    }
+}
+
+#[test]
+fn waits_while_no_key_is_pressed() {
+    let mut chip8 = chip8(Quirks::default());
+    run(&mut chip8, 10);
+    assert_eq!(chip8.pc, 0x200);
+    assert_eq!(chip8.v_registers[1], 0);
+    assert!(!chip8.is_sounding());
+}
+
+#[test]
+fn waits_for_the_key_to_be_released() {
+    let mut chip8 = chip8(Quirks::default());
+    chip8.key_state[0x5] = 1;
+    run(&mut chip8, 10);
+    assert_eq!(chip8.pc, 0x200);
+    assert_eq!(chip8.waiting_key, Some(0x5));
+
+    chip8.key_state[0x5] = 0;
+    run(&mut chip8, 1);
+    assert_eq!(chip8.pc, 0x202);
+    assert_eq!(chip8.v_registers[0], 0x5);
+    assert_eq!(chip8.waiting_key, None);
+}
+
+#[test]
+fn reads_a_held_key_once() {
+    let mut chip8 = chip8(Quirks::default());
+    chip8.key_state[0xA] = 1;
+    run(&mut chip8, 100);
+    chip8.key_state[0xA] = 0;
+    run(&mut chip8, 100);
+    assert_eq!(chip8.v_registers[0], 0xA);
+    assert_eq!(chip8.v_registers[1], 1);
+}
+
+#[test]
+fn returns_the_first_key_pressed() {
+    let mut chip8 = chip8(Quirks::default());
+    chip8.key_state[0x3] = 1;
+    run(&mut chip8, 1);
+    // a second key pressed and released while the first is held is ignored
+    chip8.key_state[0x1] = 1;
+    run(&mut chip8, 1);
+    chip8.key_state[0x1] = 0;
+    run(&mut chip8, 1);
+    assert_eq!(chip8.pc, 0x200);
+
+    chip8.key_state[0x3] = 0;
+    run(&mut chip8, 1);
+    assert_eq!(chip8.v_registers[0], 0x3);
+}
+
+#[test]
+fn sounds_the_buzzer_while_a_key_is_held() {
+    let mut chip8 = chip8(Quirks::default());
+    chip8.key_state[0x7] = 1;
+    run(&mut chip8, 1);
+    assert!(chip8.is_sounding());
+    assert_eq!(chip8.sound_timer, 0);
+
+    chip8.key_state[0x7] = 0;
+    run(&mut chip8, 1);
+    assert!(!chip8.is_sounding());
+}
+
+#[test]
+fn key_wait_on_press_quirk_returns_while_held() {
+    let mut chip8 = chip8(Quirks { key_wait_on_press: true, ..Quirks::default() });
+    chip8.key_state[0x5] = 1;
+    run(&mut chip8, 1);
+    assert_eq!(chip8.pc, 0x202);
+    assert_eq!(chip8.v_registers[0], 0x5);
+    assert!(!chip8.is_sounding());
+
+    // every pass of the loop reads the held key again
+    run(&mut chip8, 8);
+    assert_eq!(chip8.v_registers[1], 3);
+}
+
+#[test]
+fn waits_at_the_end_of_memory() {
+    // fetching the last word of XO-CHIP memory wraps pc around to 0
+    for key_wait_on_press in [false, true] {
+        let quirks = Quirks { key_wait_on_press, ..Quirks::default() };
+        let mut chip8 = Chip8::new(MachineMode::Xochip, quirks).with_pc(0xFFFE).with_opcode(0xF00A);
+        run(&mut chip8, 3);
+        assert_eq!(chip8.pc, 0xFFFE);
+    }
+}
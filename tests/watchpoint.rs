@@ -0,0 +1,82 @@
+use chip_8_emu::chip8::Chip8;
+use chip_8_emu::watchpoint::{Access, WatchHit, WatchKind, Watchpoint};
+
+// runs one instruction at 0x200 with the watchpoint set and returns what it hit
+fn hits(chip8: Chip8, start: u16, end: u16, kind: WatchKind) -> Vec<WatchHit> {
+    let mut chip8 = chip8;
+    chip8.watchpoints = vec![Watchpoint { start, end, kind }];
+    chip8.single_cycle().unwrap();
+    chip8.take_watch_hits()
+}
+
+fn hit(opcode: u16, access: Access, address: u16, old: u16, new: u16) -> WatchHit {
+    WatchHit { pc: 0x200, opcode, access, address, old, new }
+}
+
+#[test]
+fn draw_reads_the_sprite() {
+    let chip8 = Chip8::default().with_i(0x300).with_memory(0x300, &[0x80, 0xC0]).with_opcode(0xD012);
+    assert_eq!(hits(chip8, 0x300, 0x30F, WatchKind::Read), [
+        hit(0xD012, Access::Read, 0x300, 0x80, 0x80),
+        hit(0xD012, Access::Read, 0x301, 0xC0, 0xC0),
+    ]);
+}
+
+#[test]
+fn bcd_writes_its_digits() {
+    let chip8 = Chip8::default().with_v(0x1, 123).with_i(0x300).with_memory(0x301, &[9]).with_opcode(0xF133);
+    assert_eq!(hits(chip8, 0x301, 0x301, WatchKind::Write), [hit(0xF133, Access::Write, 0x301, 9, 2)]);
+}
+
+#[test]
+fn store_registers_writes_each_register() {
+    let chip8 = Chip8::default().with_v(0x0, 1).with_v(0x1, 2).with_v(0x2, 3).with_i(0x300).with_opcode(0xF255);
+    assert_eq!(hits(chip8, 0x300, 0x30F, WatchKind::ReadWrite), [
+        hit(0xF255, Access::Write, 0x300, 0, 1),
+        hit(0xF255, Access::Write, 0x301, 0, 2),
+        hit(0xF255, Access::Write, 0x302, 0, 3),
+    ]);
+}
+
+#[test]
+fn load_registers_reads_each_register() {
+    let chip8 = Chip8::default().with_i(0x300).with_memory(0x300, &[4, 5]).with_opcode(0xF165);
+    assert_eq!(hits(chip8, 0x300, 0x30F, WatchKind::Read), [
+        hit(0xF165, Access::Read, 0x300, 4, 4),
+        hit(0xF165, Access::Read, 0x301, 5, 5),
+    ]);
+}
+
+#[test]
+fn add_to_index_points_i_into_the_range() {
+    let chip8 = Chip8::default().with_v(0x0, 0x10).with_i(0x2F0).with_opcode(0xF01E);
+    assert_eq!(hits(chip8, 0x300, 0x30F, WatchKind::Write), [hit(0xF01E, Access::Index, 0x300, 0x2F0, 0x300)]);
+}
+
+#[test]
+fn load_index_points_i_into_the_range() {
+    let chip8 = Chip8::default().with_i(0x123).with_opcode(0xA30F);
+    assert_eq!(hits(chip8, 0x300, 0x30F, WatchKind::Read), [hit(0xA30F, Access::Index, 0x30F, 0x123, 0x30F)]);
+}
+
+#[test]
+fn ranges_do_not_fire_just_outside() {
+    // writes 0x2FF to 0x304, only 0x301 and 0x302 are watched
+    let chip8 = Chip8::default().with_i(0x2FF).with_opcode(0xF555);
+    let addresses: Vec<u16> = hits(chip8, 0x301, 0x302, WatchKind::Write).iter().map(|hit| hit.address).collect();
+    assert_eq!(addresses, [0x301, 0x302]);
+
+    assert!(hits(Chip8::default().with_opcode(0xA300), 0x301, 0x302, WatchKind::ReadWrite).is_empty());
+    assert!(hits(Chip8::default().with_opcode(0xA303), 0x301, 0x302, WatchKind::ReadWrite).is_empty());
+}
+
+#[test]
+fn kinds_only_fire_on_their_access() {
+    let store = || Chip8::default().with_i(0x300).with_opcode(0xF055);
+    assert!(hits(store(), 0x300, 0x300, WatchKind::Read).is_empty());
+    assert_eq!(hits(store(), 0x300, 0x300, WatchKind::Write).len(), 1);
+
+    let load = || Chip8::default().with_i(0x300).with_opcode(0xF065);
+    assert!(hits(load(), 0x300, 0x300, WatchKind::Write).is_empty());
+    assert_eq!(hits(load(), 0x300, 0x300, WatchKind::Read).len(), 1);
+}